@@ -0,0 +1,53 @@
+//! Builds the core types and directives of `beancount_core_lib` without its `std` feature, to
+//! check that they stay usable in `no_std` + `alloc` environments.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use beancount_core_lib::account::Account;
+use beancount_core_lib::amount::{sum_amounts, Amount};
+use beancount_core_lib::date::Date;
+use beancount_core_lib::directives::balance::Balance;
+use rust_decimal::Decimal;
+
+/// Returns the balance assertion of `account` on `date`, after depositing `deposits` and
+/// withdrawing `withdrawal`.
+pub fn balance_after(
+    account: &str,
+    date: &str,
+    deposits: &[Decimal],
+    withdrawal: Decimal,
+) -> Option<Balance<'static>> {
+    let date: Date = date.parse().ok()?;
+    let mut amounts: Vec<Amount> = deposits
+        .iter()
+        .map(|num| Amount::from((*num, "USD")))
+        .collect();
+    amounts.push(Amount::from((-withdrawal, "USD")));
+    let total = sum_amounts(amounts).remove("USD")?;
+    Some(Balance::on(date, Account::from(account), total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_after() {
+        let balance = balance_after(
+            "Assets:US:BofA:Checking",
+            "2014/07/09",
+            &[Decimal::new(10000, 2), Decimal::new(5420, 2)],
+            Decimal::from(100),
+        )
+        .unwrap();
+        assert_eq!(balance.account, Account::from("Assets:US:BofA:Checking"));
+        assert_eq!(balance.date, Date::from_str_unchecked("2014-07-09"));
+        assert_eq!(
+            balance.to_string(),
+            "2014-07-09 balance Assets:US:BofA:Checking 54.20 USD"
+        );
+    }
+}
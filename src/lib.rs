@@ -1,7 +1,41 @@
+//! With the default `std` feature disabled, the crate is `no_std` and only needs `alloc`: the
+//! core types, directives, inventories, metadata and the price database are all available. The
+//! `Ledger`, `AccountIndex`, `AccountInterner` and `detect_include_cycles` require `std`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+#[cfg(feature = "std")]
+use std::{cmp, io};
+
+#[cfg(feature = "std")]
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+#[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 pub mod types;
 pub use types::*;
 
+use self::{account::Account, currency::Currency, date::Date};
+#[cfg(feature = "std")]
+use self::{
+    account::{AccountIndex, AccountInterner, AccountType, RootNames},
+    amount::Amount,
+    directives::{
+        balance::Balance, close::Close, open::Open, pad::Pad, position::Position, posting::Posting,
+        transaction::Transaction, Directive, DirectiveVisitor, DirectiveVisitorMut,
+    },
+    flags::Flag,
+    inventory::Inventory,
+    metadata::{Meta, MetaValue},
+    price_db::PriceDB,
+};
+
 /// # Directives
 ///
 /// Beancount is a declarative language. The input consists of a text file containing mainly a list
@@ -60,12 +94,984 @@ pub use types::*;
 ///
 /// This also explains why balance assertions are verified before any transactions that occur on
 /// the same date. This is for consistency.
+#[cfg(feature = "std")]
 #[derive(Clone, Debug, PartialEq, Default, TypedBuilder)]
-pub struct Ledger {
-    pub directives: Vec<directives::Directive>,
+pub struct Ledger<'a> {
+    pub directives: Vec<directives::Directive<'a>>,
+
+    /// Short names standing for accounts, e.g. the codes emitted by an importer. See
+    /// [Ledger::resolve_alias].
+    #[builder(default)]
+    pub aliases: HashMap<String, Account>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Ledger<'a> {
+    /// Returns the number of directives in the ledger.
+    pub fn len(&self) -> usize {
+        self.directives.len()
+    }
+
+    /// Returns `true` if the ledger has no directives.
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty()
+    }
+
+    /// Streams the text of the ledger to `w`, one directive at a time. The output is the same as
+    /// the `Display` implementation, without building the whole ledger as a single `String`.
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        write!(w, "{}", self)
+    }
+
+    /// Writes every posting of the ledger as a CSV row, after a header row with the columns
+    /// `date,flag,payee,narration,account,amount,currency,cost,price`. The flag, payee and
+    /// narration are those of the transaction; the cost and price are written as in beancount,
+    /// e.g. `{183.07 USD}` and `197.90 USD`. Missing values are left empty, and fields are quoted
+    /// when they contain a comma, a double quote or a line break.
+    pub fn postings_to_csv(&self, w: &mut impl io::Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "date,flag,payee,narration,account,amount,currency,cost,price"
+        )?;
+        for directive in &self.directives {
+            let t = match directive {
+                Directive::Transaction(t) => t,
+                _ => continue,
+            };
+            for posting in &t.postings {
+                let fields = [
+                    t.date.to_string(),
+                    t.flag.to_string(),
+                    t.payee.as_deref().unwrap_or_default().to_string(),
+                    t.narration.to_string(),
+                    posting.account.to_string(),
+                    posting.units.num.map(|n| n.to_string()).unwrap_or_default(),
+                    posting.units.currency.clone().unwrap_or_default(),
+                    posting
+                        .cost
+                        .as_ref()
+                        .map(|c| c.to_string())
+                        .unwrap_or_default(),
+                    posting
+                        .price
+                        .as_ref()
+                        .map(|p| p.to_string())
+                        .unwrap_or_default(),
+                ];
+                let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                writeln!(w, "{}", fields.join(","))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the ledger like `Display`, but with the posting amounts of transactions aligned
+    /// on `amount_column`. See `Transaction::to_formatted_string`.
+    pub fn to_formatted_string(&self, amount_column: usize) -> String {
+        let mut res = String::new();
+        self.write_directives(&mut res, |d| match d {
+            Directive::Transaction(t) => t.to_formatted_string(amount_column),
+            d => d.to_string(),
+        })
+        .expect("writing to a String cannot fail");
+        res
+    }
+
+    /// Writes the text produced by `render` for every directive, each followed by a newline,
+    /// with a blank line between directives.
+    fn write_directives(
+        &self,
+        f: &mut impl fmt::Write,
+        render: impl Fn(&Directive) -> String,
+    ) -> fmt::Result {
+        let mut first = true;
+        for directive in &self.directives {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            writeln!(f, "{}", render(directive).trim_end_matches('\n'))?;
+        }
+        Ok(())
+    }
+
+    /// Returns every posting of every transaction in the ledger, each paired with the date of the
+    /// transaction it belongs to. Postings are returned in directive order.
+    pub fn all_postings(&self) -> Vec<(&Date, &Posting)> {
+        self.directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Transaction(t) => Some(t),
+                _ => None,
+            })
+            .flat_map(|t| t.postings.iter().map(move |p| (&t.date, p)))
+            .collect()
+    }
+
+    /// Compares two ledgers directive by directive, ignoring the source text kept on options,
+    /// plugins and includes. Metadata maps compare equal regardless of their ordering.
+    pub fn semantic_eq(&self, other: &Ledger) -> bool {
+        self.directives.len() == other.directives.len()
+            && self
+                .directives
+                .iter()
+                .zip(&other.directives)
+                .all(|pair| match pair {
+                    (Directive::Option(a), Directive::Option(b)) => {
+                        a.name == b.name && a.val == b.val
+                    }
+                    (Directive::Plugin(a), Directive::Plugin(b)) => {
+                        a.module == b.module && a.config == b.config
+                    }
+                    (Directive::Include(a), Directive::Include(b)) => a.filename == b.filename,
+                    (a, b) => a == b,
+                })
+    }
+
+    /// Replaces the account aliases of the ledger with `map`.
+    pub fn set_aliases(&mut self, map: HashMap<String, Account>) {
+        self.aliases = map;
+    }
+
+    /// Returns the account `name` stands for, if it is a configured alias.
+    pub fn resolve_alias(&self, name: &str) -> Option<&Account> {
+        self.aliases.get(name)
+    }
+
+    /// Sets `flag` on every transaction matching `pred`, e.g. to mark reviewed transactions as
+    /// `*`. The flags of individual postings are left unchanged.
+    pub fn mark_transactions<F: Fn(&Transaction) -> bool>(&mut self, pred: F, flag: Flag) {
+        for directive in &mut self.directives {
+            if let Directive::Transaction(t) = directive {
+                if pred(t) {
+                    t.flag = flag.clone();
+                }
+            }
+        }
+    }
+
+    /// Sets the metadata pushed by `pushmeta` directives on every transaction that follows
+    /// them, and on its postings, until the matching `popmeta`. Metadata already written on a
+    /// transaction or posting takes precedence. The `pushmeta` and `popmeta` directives are kept.
+    pub fn apply_pushed_meta(&mut self) {
+        let mut pushed: Vec<(String, MetaValue)> = Vec::new();
+        for directive in &mut self.directives {
+            match directive {
+                Directive::PushMeta(p) => pushed.push((p.key.clone(), p.value.clone())),
+                Directive::PopMeta(p) => {
+                    if let Some(i) = pushed.iter().rposition(|(key, _)| *key == p.key) {
+                        pushed.remove(i);
+                    }
+                }
+                Directive::Transaction(t) => {
+                    let metas = std::iter::once(&mut t.meta)
+                        .chain(t.postings.iter_mut().map(|p| &mut p.meta));
+                    for meta in metas {
+                        for (key, value) in pushed.iter().rev() {
+                            meta.entry(key.clone()).or_insert_with(|| value.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Compares the directives of two ledgers, regardless of their order. A directive repeated
+    /// in both ledgers is matched as many times as it appears in both.
+    pub fn diff<'b>(&'b self, other: &'b Ledger) -> LedgerDiff<'b> {
+        let mut matched = vec![false; other.directives.len()];
+        let mut diff = LedgerDiff::default();
+        for directive in &self.directives {
+            let found = other
+                .directives
+                .iter()
+                .enumerate()
+                .position(|(i, d)| !matched[i] && d == directive);
+            match found {
+                Some(i) => {
+                    matched[i] = true;
+                    diff.common.push(directive);
+                }
+                None => diff.removed.push(directive),
+            }
+        }
+        diff.added = other
+            .directives
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(d, _)| d)
+            .collect();
+        diff
+    }
+
+    /// Returns the accounts that are closed on the same day they are opened. Since directives
+    /// apply at the beginning of the day, such a close would have to be moved to the next day.
+    pub fn invalid_same_day_closes(&self) -> Vec<(&Open<'a>, &Close<'a>)> {
+        let closes: Vec<&Close> = self
+            .directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Close(c) => Some(c),
+                _ => None,
+            })
+            .collect();
+        self.directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Open(o) => Some(o),
+                _ => None,
+            })
+            .flat_map(|o| {
+                closes
+                    .iter()
+                    .filter(move |c| c.account == o.account && c.date == o.date)
+                    .map(move |c| (o, *c))
+            })
+            .collect()
+    }
+
+    /// Checks every posting against the currencies its account was opened with, and returns
+    /// one error per posting in a currency the account does not allow. Accounts opened without
+    /// currencies, or not opened at all, allow any currency.
+    pub fn validate_currency_constraints(&self) -> Result<(), Vec<CurrencyConstraintError>> {
+        let mut allowed: HashMap<&Account, &Vec<Currency>> = HashMap::new();
+        for directive in &self.directives {
+            if let Directive::Open(o) = directive {
+                allowed.entry(&o.account).or_insert(&o.currencies);
+            }
+        }
+        let mut errors = Vec::new();
+        for t in self.transactions() {
+            for posting in &t.postings {
+                let (Some(currencies), Some(currency)) =
+                    (allowed.get(&posting.account), &posting.units.currency)
+                else {
+                    continue;
+                };
+                if !currencies.is_empty() && !currencies.contains(currency) {
+                    errors.push(CurrencyConstraintError {
+                        account: posting.account.clone(),
+                        used_currency: currency.clone(),
+                        allowed: currencies.to_vec(),
+                        date: t.date.clone(),
+                        narration: t.narration.to_string(),
+                    });
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the balance of every account with postings on or before `date`, as a trial
+    /// balance report would show it. Accounts whose postings cancel out map to an empty
+    /// inventory.
+    pub fn trial_balance(&self, date: &Date) -> BTreeMap<Account, Inventory> {
+        let mut balances: BTreeMap<Account, Inventory> = BTreeMap::new();
+        for (t, account, position) in self.booked_positions() {
+            if &t.date <= date {
+                balances
+                    .entry(account.clone())
+                    .or_default()
+                    .add_position(position);
+            }
+        }
+        balances
+    }
+
+    /// Returns the lots held at cost in `Assets` accounts on `date`, grouped by commodity and
+    /// sorted by acquisition date. Lots of the same commodity in different accounts are listed
+    /// separately.
+    pub fn holdings(&self, date: &Date) -> BTreeMap<Currency, Vec<Position>> {
+        let mut holdings: BTreeMap<Currency, Vec<Position>> = BTreeMap::new();
+        for (account, inventory) in self.trial_balance(date) {
+            if account.account_type != AccountType::Assets {
+                continue;
+            }
+            for position in inventory.positions() {
+                if position.cost.is_some() {
+                    holdings
+                        .entry(position.units.currency.clone())
+                        .or_default()
+                        .push(position.clone());
+                }
+            }
+        }
+        for lots in holdings.values_mut() {
+            lots.sort_by(Position::cmp_by_date);
+        }
+        holdings
+    }
+
+    /// Returns the units held per currency by every account with postings on or before `date`,
+    /// and by each of their ancestors up to the root account, where a parent holds the sum of
+    /// its descendants as in a balance sheet tree.
+    pub fn rolled_up_balances(
+        &self,
+        date: &Date,
+    ) -> BTreeMap<Account, BTreeMap<Currency, Decimal>> {
+        let mut balances: BTreeMap<Account, BTreeMap<Currency, Decimal>> = BTreeMap::new();
+        for (account, inventory) in self.trial_balance(date) {
+            for depth in 0..=account.parts.len() {
+                let ancestor = Account::from_parts_unchecked(
+                    account.account_type,
+                    account.parts[..depth].to_vec(),
+                );
+                let balance = balances.entry(ancestor).or_default();
+                for position in inventory.positions() {
+                    *balance.entry(position.units.currency.clone()).or_default() +=
+                        position.units.num;
+                }
+            }
+        }
+        balances
+    }
+
+    /// Returns each pair of consecutive balance assertions of `account` in the same currency,
+    /// in date order, with the change they imply and the change made by the postings between
+    /// them. Since assertions apply at the beginning of their day, the postings counted are
+    /// those dated from the first assertion up to the day before the second.
+    pub fn balance_gaps(&self, account: &Account) -> Vec<BalanceGap<'_>> {
+        let mut balances: Vec<&Balance> = self
+            .directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Balance(b) if &b.account == account => Some(b),
+                _ => None,
+            })
+            .collect();
+        balances.sort_by(|a, b| a.date.cmp(&b.date));
+
+        let booked = self.booked_positions();
+        let mut gaps = Vec::new();
+        for (i, from) in balances.iter().enumerate() {
+            let Some(to) = balances[i + 1..]
+                .iter()
+                .find(|b| b.amount.currency == from.amount.currency)
+            else {
+                continue;
+            };
+            let transaction_delta = booked
+                .iter()
+                .filter(|(t, a, _)| *a == account && t.date >= from.date && t.date < to.date)
+                .filter(|(_, _, p)| p.units.currency == from.amount.currency)
+                .map(|(_, _, p)| p.units.num)
+                .sum();
+            gaps.push(BalanceGap {
+                from,
+                to,
+                asserted_delta: to.amount.num - from.amount.num,
+                transaction_delta,
+            });
+        }
+        gaps
+    }
+
+    /// Returns the transactions inserted by the `pad` directives of the ledger, flagged `P` as in
+    /// beancount. Each pad fills the difference between the balance of its account and the first
+    /// balance assertion of each currency that follows it, before the next pad of the account.
+    /// `pad_to_account` receives the difference and `pad_from_account` the opposite amount. The
+    /// difference is negative when padding down, e.g. a credit card to the debt on a statement.
+    pub fn resolve_pads(&self) -> Vec<Transaction<'a>> {
+        let mut pads: Vec<&Pad> = self
+            .directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Pad(p) => Some(p),
+                _ => None,
+            })
+            .collect();
+        pads.sort_by(|a, b| a.date.cmp(&b.date));
+        let mut balances: Vec<&Balance> = self
+            .directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Balance(b) => Some(b),
+                _ => None,
+            })
+            .collect();
+        balances.sort_by(|a, b| a.date.cmp(&b.date));
+
+        let booked = self.booked_positions();
+        let mut padding: Vec<Transaction<'a>> = Vec::new();
+        for (i, pad) in pads.iter().enumerate() {
+            let next_pad = pads[i + 1..]
+                .iter()
+                .find(|p| p.pad_to_account == pad.pad_to_account && p.date > pad.date);
+            let mut padded: BTreeSet<&Currency> = BTreeSet::new();
+            for balance in &balances {
+                if balance.account != pad.pad_to_account
+                    || balance.date <= pad.date
+                    || next_pad.is_some_and(|p| balance.date > p.date)
+                    || !padded.insert(&balance.amount.currency)
+                {
+                    continue;
+                }
+                let booked_units: Decimal = booked
+                    .iter()
+                    .filter(|(t, a, _)| t.date < balance.date && *a == &pad.pad_to_account)
+                    .filter(|(_, _, p)| p.units.currency == balance.amount.currency)
+                    .map(|(_, _, p)| p.units.num)
+                    .sum();
+                let padded_units: Decimal = padding
+                    .iter()
+                    .filter(|t| t.date < balance.date)
+                    .flat_map(|t| &t.postings)
+                    .filter(|p| p.account == pad.pad_to_account)
+                    .filter(|p| p.units.currency.as_ref() == Some(&balance.amount.currency))
+                    .filter_map(|p| p.units.num)
+                    .sum();
+                let current = booked_units + padded_units;
+                let missing = balance.amount.num - current;
+                if missing.is_zero() {
+                    continue;
+                }
+                let mut transaction = Transaction::simple(
+                    pad.date.clone(),
+                    Flag::Other("P".to_string()),
+                    format!("(Padding inserted for balance of {})", balance.amount),
+                    pad.pad_from_account.clone(),
+                    pad.pad_to_account.clone(),
+                    Amount {
+                        num: missing,
+                        currency: balance.amount.currency.clone(),
+                    },
+                );
+                transaction.postings.reverse();
+                padding.push(transaction);
+            }
+        }
+        padding
+    }
+
+    /// Returns the opening balance of `account`, i.e. the balance it received from padding and
+    /// opening transactions before its first other posting. An opening transaction is one whose
+    /// other postings all go to `Equity` accounts, e.g. `Equity:Opening-Balances`. Returns `None`
+    /// if the account has no opening balance, or one in several currencies; see
+    /// [Ledger::opening_balances] for those.
+    pub fn opening_balance(&self, account: &Account) -> Option<Amount> {
+        let mut balances = self.opening_balances(account).into_iter();
+        match (balances.next(), balances.next()) {
+            (Some((currency, num)), None) => Some(Amount { num, currency }),
+            _ => None,
+        }
+    }
+
+    /// Returns the opening balance of `account` per currency, as [Ledger::opening_balance]
+    /// defines it. The map is empty if the account has no opening balance.
+    pub fn opening_balances(&self, account: &Account) -> BTreeMap<Currency, Decimal> {
+        let is_opening = |t: &Transaction| {
+            t.postings.iter().any(|p| &p.account == account)
+                && t.postings
+                    .iter()
+                    .all(|p| &p.account == account || p.account.account_type == AccountType::Equity)
+        };
+        let booked: Vec<_> = self
+            .booked_positions()
+            .into_iter()
+            .filter(|(_, a, _)| *a == account)
+            .collect();
+        let first_use = booked
+            .iter()
+            .filter(|(t, _, _)| !is_opening(t))
+            .map(|(t, _, _)| &t.date)
+            .min();
+        let before_first_use = |date: &Date| first_use.is_none() || Some(date) <= first_use;
+
+        let mut balance: BTreeMap<Currency, Decimal> = BTreeMap::new();
+        let opening = booked
+            .iter()
+            .filter(|(t, _, _)| is_opening(t) && before_first_use(&t.date))
+            .map(|(_, _, p)| p.units.clone());
+        let padding = self.resolve_pads();
+        let padded = padding
+            .iter()
+            .filter(|t| before_first_use(&t.date))
+            .flat_map(|t| &t.postings)
+            .filter(|p| &p.account == account)
+            .filter_map(|p| Amount::try_from(p.units.clone()).ok());
+        for amount in opening.chain(padded) {
+            *balance.entry(amount.currency).or_default() += amount.num;
+        }
+        balance.retain(|_, num| !num.is_zero());
+        balance
+    }
+
+    /// Returns the distinct payees of the transactions, e.g. to offer them for completion in an
+    /// editor. Blank payees are left out.
+    pub fn payees(&self) -> BTreeSet<String> {
+        self.transactions()
+            .filter_map(|t| t.payee.as_deref())
+            .filter(|p| !p.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Returns the distinct narrations of the transactions, leaving out blank ones.
+    pub fn narrations(&self) -> BTreeSet<String> {
+        self.transactions()
+            .map(|t| t.narration.as_ref())
+            .filter(|n| !n.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Iterates over the transactions of the ledger, in directive order.
+    fn transactions(&self) -> impl Iterator<Item = &Transaction<'a>> {
+        self.directives.iter().filter_map(|d| match d {
+            Directive::Transaction(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    /// Books the transactions in date order, as beancount does before computing balances: each
+    /// posting at cost is booked with [Inventory::book] against the lots held by its account, and
+    /// the posting without a number receives what balances the transaction, one position per
+    /// currency, as [Transaction::try_balanced] fills it. Returns each booked position with its
+    /// transaction and account.
+    fn booked_positions(&self) -> Vec<(&Transaction<'a>, &Account, Position)> {
+        let mut transactions: Vec<&Transaction<'a>> = self.transactions().collect();
+        transactions.sort_by(|a, b| a.date.cmp(&b.date));
+
+        let mut inventories: HashMap<&Account, Inventory> = HashMap::new();
+        let mut booked = Vec::new();
+        for t in transactions {
+            let mut residuals: BTreeMap<Currency, Decimal> = BTreeMap::new();
+            let mut elided = None;
+            for posting in &t.postings {
+                if posting.units.num.is_none() {
+                    elided = elided.or(Some(&posting.account));
+                    continue;
+                }
+                let Ok(units) = Amount::try_from(posting.units.clone()) else {
+                    continue;
+                };
+                let inventory = inventories.entry(&posting.account).or_default();
+                for position in inventory.book(units, posting.cost.as_ref(), &t.date) {
+                    let weight = match &position.cost {
+                        Some(cost) => Some(Amount {
+                            num: position.units.num * cost.number,
+                            currency: cost.currency.clone(),
+                        }),
+                        None => posting.weight(),
+                    };
+                    if let Some(weight) = weight {
+                        *residuals.entry(weight.currency).or_default() += weight.num;
+                    }
+                    booked.push((t, &posting.account, position));
+                }
+            }
+            let Some(account) = elided else {
+                continue;
+            };
+            for (currency, num) in residuals {
+                if num.is_zero() {
+                    continue;
+                }
+                let position = Position {
+                    units: Amount {
+                        num: -num,
+                        currency,
+                    },
+                    cost: None,
+                };
+                inventories
+                    .entry(account)
+                    .or_default()
+                    .add_position(position.clone());
+                booked.push((t, account, position));
+            }
+        }
+        booked
+    }
+
+    /// Builds the price database from the `price` directives of the ledger.
+    pub fn price_db(&self) -> PriceDB {
+        self.directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Price(p) => Some(p),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the dated directives dated after `today`, in directive order, e.g. to catch an
+    /// importer writing transactions into the future.
+    pub fn future_dated(&self, today: &Date) -> Vec<&Directive<'a>> {
+        self.directives
+            .iter()
+            .filter(|d| {
+                d.as_entry()
+                    .and_then(|e| e.date())
+                    .is_some_and(|date| date > today)
+            })
+            .collect()
+    }
+
+    /// Returns the balance assertions made on Income or Expenses accounts. These accounts track
+    /// flows over a period rather than a position, so asserting their balance is rarely
+    /// meaningful.
+    pub fn suspicious_balances(&self) -> Vec<&Balance<'a>> {
+        self.directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Balance(b)
+                    if matches!(
+                        b.account.account_type,
+                        AccountType::Income | AccountType::Expenses
+                    ) =>
+                {
+                    Some(b)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the root account names in effect, applying the `name_*` options of the ledger
+    /// to the English defaults.
+    pub fn root_names(&self) -> RootNames {
+        let mut names = RootNames::default();
+        for directive in &self.directives {
+            if let Directive::Option(option) = directive {
+                if let Some((account_type, name)) = option.root_name_change() {
+                    names.set(account_type, name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Iterates over the directives as [Entry](directives::Entry) trait objects, skipping
+    /// unsupported ones.
+    pub fn entries(&self) -> impl Iterator<Item = &dyn directives::Entry> {
+        self.directives.iter().filter_map(Directive::as_entry)
+    }
+
+    /// Builds an index of every account referenced by the ledger, for repeated membership and
+    /// hierarchy queries without rescanning the directives.
+    pub fn account_index(&self) -> AccountIndex {
+        self.account_references()
+            .into_iter()
+            .map(|(_, account)| account.clone())
+            .collect()
+    }
+
+    /// Interns every account referenced by the ledger, in order of first reference. The ledger
+    /// itself is left as is; the interner maps its accounts to compact handles.
+    pub fn intern_accounts(&self) -> AccountInterner {
+        let mut interner = AccountInterner::new();
+        for (_, account) in self.account_references() {
+            interner.intern(account);
+        }
+        interner
+    }
+
+    /// Passes every directive of the ledger to `visitor`, in order.
+    pub fn accept(&self, visitor: &mut impl DirectiveVisitor) {
+        for directive in &self.directives {
+            directive.accept(visitor);
+        }
+    }
+
+    /// Passes every directive of the ledger to `visitor` to be rewritten in place, in order.
+    pub fn accept_mut(&mut self, visitor: &mut impl DirectiveVisitorMut) {
+        for directive in &mut self.directives {
+            directive.accept_mut(visitor);
+        }
+    }
+
+    /// Returns the earliest date of a directive referencing `account`, including its `open`, or
+    /// `None` if the ledger never references it.
+    pub fn first_usage_date(&self, account: &Account) -> Option<Date> {
+        self.account_references()
+            .into_iter()
+            .filter(|(_, other)| *other == account)
+            .map(|(date, _)| date)
+            .min()
+            .cloned()
+    }
+
+    /// Returns an `open` directive for every account referenced by the ledger without one, in
+    /// chart-of-accounts order. Each is dated `default_date`, or the first date the account is
+    /// used if that is earlier, so that no posting precedes its open.
+    pub fn generate_missing_opens(&self, default_date: &Date) -> Vec<Open<'a>> {
+        let references = self.account_references();
+        let opened: BTreeSet<&Account> = self
+            .directives
+            .iter()
+            .filter_map(|d| match d {
+                Directive::Open(o) => Some(&o.account),
+                _ => None,
+            })
+            .collect();
+        let mut first_use: BTreeMap<&Account, &Date> = BTreeMap::new();
+        for (date, account) in references {
+            if opened.contains(account) {
+                continue;
+            }
+            let first = first_use.entry(account).or_insert(date);
+            if date < *first {
+                *first = date;
+            }
+        }
+        first_use
+            .into_iter()
+            .map(|(account, date)| {
+                Open::builder()
+                    .date(cmp::min(date, default_date).clone())
+                    .account(account.clone())
+                    .build()
+            })
+            .collect()
+    }
+
+    /// Returns the implicit zero balance assertion of every opened account at its opening date,
+    /// in directive order. One assertion is made per currency allowed by the `open`, or per
+    /// currency posted to the account if it is unconstrained, so that postings dated before the
+    /// `open` fail the check.
+    pub fn implicit_opening_assertions(&self) -> Vec<Balance<'a>> {
+        let mut posted: HashMap<&Account, BTreeSet<&Currency>> = HashMap::new();
+        for (_, posting) in self.all_postings() {
+            if let Some(currency) = &posting.units.currency {
+                posted.entry(&posting.account).or_default().insert(currency);
+            }
+        }
+        let mut assertions = Vec::new();
+        for directive in &self.directives {
+            let open = match directive {
+                Directive::Open(o) => o,
+                _ => continue,
+            };
+            let currencies: BTreeSet<&Currency> = if open.currencies.is_empty() {
+                posted.get(&open.account).cloned().unwrap_or_default()
+            } else {
+                open.currencies.iter().collect()
+            };
+            assertions.extend(currencies.into_iter().map(|currency| {
+                Balance::builder()
+                    .date(open.date.clone())
+                    .account(open.account.clone())
+                    .amount(Amount {
+                        num: Decimal::ZERO,
+                        currency: currency.clone(),
+                    })
+                    .build()
+            }));
+        }
+        assertions
+    }
+
+    /// Returns true if no account referenced by the ledger is nested under `account`, e.g.
+    /// `Assets:US:BofA` is not a leaf once `Assets:US:BofA:Checking` is used.
+    pub fn is_leaf_account(&self, account: &Account) -> bool {
+        !self
+            .account_references()
+            .into_iter()
+            .any(|(_, other)| account.is_ancestor_of(other))
+    }
+
+    /// Returns every reference to an account in the ledger, with the date of the directive
+    /// making it, in directive order.
+    fn account_references(&self) -> Vec<(&Date, &Account)> {
+        let mut references = Vec::new();
+        for directive in &self.directives {
+            match directive {
+                Directive::Open(d) => references.push((&d.date, &d.account)),
+                Directive::Close(d) => references.push((&d.date, &d.account)),
+                Directive::Balance(d) => references.push((&d.date, &d.account)),
+                Directive::Note(d) => references.push((&d.date, &d.account)),
+                Directive::Document(d) => references.push((&d.date, &d.account)),
+                Directive::Pad(d) => {
+                    references.push((&d.date, &d.pad_to_account));
+                    references.push((&d.date, &d.pad_from_account));
+                }
+                Directive::Transaction(t) => {
+                    references.extend(t.postings.iter().map(|p| (&t.date, &p.account)))
+                }
+                _ => {}
+            }
+        }
+        references
+    }
+
+    /// Returns the metadata of the `commodity` directive declaring `currency`, if there is one.
+    pub fn commodity_meta(&self, currency: &Currency) -> Option<&Meta> {
+        self.directives.iter().find_map(|d| match d {
+            Directive::Commodity(c) if &c.name == currency => Some(&c.meta),
+            _ => None,
+        })
+    }
+
+    /// Returns the display precision declared for `currency` by a `precision` metadata number on
+    /// its `commodity` directive, e.g. `precision: 2`, to round amounts in reports with
+    /// `Amount::with_scale`.
+    pub fn commodity_precision(&self, currency: &Currency) -> Option<u32> {
+        match self.commodity_meta(currency)?.get("precision")? {
+            MetaValue::Number(n) if n.fract().is_zero() => n.to_u32(),
+            _ => None,
+        }
+    }
+
+    /// Returns the currencies used in amounts, costs and prices that are not declared by a
+    /// `commodity` directive. This mirrors beancount's `check_commodity` plugin.
+    pub fn undeclared_commodities(&self) -> BTreeSet<Currency> {
+        let mut declared = BTreeSet::new();
+        let mut used = BTreeSet::new();
+        for directive in &self.directives {
+            match directive {
+                Directive::Commodity(c) => {
+                    declared.insert(&c.name);
+                }
+                Directive::Transaction(t) => {
+                    for posting in &t.postings {
+                        used.extend(posting.units.currency.as_ref());
+                        used.extend(posting.cost.as_ref().and_then(|c| c.currency.as_ref()));
+                        used.extend(posting.price.as_ref().and_then(|p| p.currency.as_ref()));
+                    }
+                }
+                Directive::Balance(b) => {
+                    used.insert(&b.amount.currency);
+                }
+                Directive::Price(p) => {
+                    used.insert(&p.currency);
+                    used.insert(&p.amount.currency);
+                }
+                _ => {}
+            }
+        }
+        used.difference(&declared).map(|c| c.to_string()).collect()
+    }
+}
+
+/// Renders every directive followed by a newline, with a blank line between directives.
+/// Unsupported directives are written as the raw text they hold.
+#[cfg(feature = "std")]
+impl fmt::Display for Ledger<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_directives(f, |d| d.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> IntoIterator for Ledger<'a> {
+    type Item = Directive<'a>;
+    type IntoIter = std::vec::IntoIter<Directive<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.directives.into_iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'l, 'a> IntoIterator for &'l Ledger<'a> {
+    type Item = &'l Directive<'a>;
+    type IntoIter = std::slice::Iter<'l, Directive<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.directives.iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'l, 'a> IntoIterator for &'l mut Ledger<'a> {
+    type Item = &'l mut Directive<'a>;
+    type IntoIter = std::slice::IterMut<'l, Directive<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.directives.iter_mut()
+    }
+}
+
+/// The result of [Ledger::diff]: the directives only in the new ledger, only in the old one,
+/// and in both.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LedgerDiff<'a> {
+    /// Directives of the other ledger missing from this one, in the other ledger's order.
+    pub added: Vec<&'a Directive<'a>>,
+    /// Directives of this ledger missing from the other one.
+    pub removed: Vec<&'a Directive<'a>>,
+    /// Directives present in both ledgers, in this ledger's order.
+    pub common: Vec<&'a Directive<'a>>,
+}
+
+#[cfg(feature = "std")]
+impl LedgerDiff<'_> {
+    /// Returns true if both ledgers have the same directives.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A posting found by [Ledger::validate_currency_constraints] in a currency its account does
+/// not allow.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CurrencyConstraintError {
+    pub account: Account,
+    pub used_currency: Currency,
+    /// The currencies the account was opened with.
+    pub allowed: Vec<Currency>,
+    /// The date of the transaction of the posting.
+    pub date: Date,
+    /// The narration of the transaction of the posting.
+    pub narration: String,
+}
+
+impl fmt::Display for CurrencyConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} \"{}\": {} does not allow {} (allowed: {})",
+            self.date,
+            self.narration,
+            self.account,
+            self.used_currency,
+            self.allowed.join(", ")
+        )
+    }
+}
+
+impl core::error::Error for CurrencyConstraintError {}
+
+/// Two consecutive balance assertions of an account, found by [Ledger::balance_gaps].
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceGap<'a> {
+    pub from: &'a Balance<'a>,
+    pub to: &'a Balance<'a>,
+    /// The change between the two asserted amounts.
+    pub asserted_delta: Decimal,
+    /// The sum of the postings to the account between the two assertions.
+    pub transaction_delta: Decimal,
 }
 
-#[cfg(test)]
+#[cfg(feature = "std")]
+impl BalanceGap<'_> {
+    /// Returns true if the postings do not account for the asserted change.
+    pub fn is_mismatch(&self) -> bool {
+        self.asserted_delta != self.transaction_delta
+    }
+}
+
+/// Quotes `field` for a CSV file if it contains a comma, a double quote or a line break,
+/// doubling the double quotes inside.
+#[cfg(feature = "std")]
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
 
     use rust_decimal::Decimal;
@@ -73,18 +1079,55 @@ mod tests {
     use crate::{
         amount::{Amount, IncompleteAmount},
         currency::Currency,
-        directives::{balance::Balance, note::Note, posting::Posting, transaction::Transaction},
+        directives::{
+            balance::Balance,
+            beancount_option::BcOption,
+            close::Close,
+            commodity::Commodity,
+            document::Document,
+            include::Include,
+            note::Note,
+            pad::Pad,
+            plugin::Plugin,
+            posting::Posting,
+            pushmeta::{PopMeta, PushMeta},
+            transaction::Transaction,
+        },
         flags::Flag,
+        metadata::{Meta, MetaValue},
     };
 
-    use self::{
-        account::Account,
-        date::Date,
-        directives::open::{Open, OpenBuilder},
-    };
+    use self::{date::Date, directives::open::Open};
 
     use super::*;
 
+    /// A 37.45 USD lunch on `date`, paid with the credit card.
+    fn lunch(date: &str) -> Transaction<'static> {
+        Transaction::simple(
+            Date::from_str_unchecked(date),
+            Flag::Okay,
+            "Lunch".to_string(),
+            Account::from("Liabilities:CreditCard"),
+            Account::from("Expenses:Food:Restaurant"),
+            Amount::from((Decimal::new(3745, 2), "USD")),
+        )
+    }
+
+    /// A posting of `units` to `account`, e.g. `-10 IVV`, at the cost spec `cost` if there is
+    /// one, e.g. `{183.07 USD}`. Empty units leave the amount out, for it to be filled in.
+    fn posting(account: &str, units: &str, cost: Option<&str>) -> Posting {
+        let units = if units.is_empty() {
+            IncompleteAmount::builder().build()
+        } else {
+            units.parse::<Amount>().unwrap().into()
+        };
+        Posting::builder()
+            .account(Account::from(account))
+            .units(units)
+            .cost(cost.map(|c| c.parse().unwrap()))
+            .build()
+    }
+
     #[test]
     fn it_works() {
         let ledger = Ledger::default();
@@ -195,6 +1238,1578 @@ mod tests {
                     .build(),
             ])
             .build();
-        println!("{}", t.to_string());
+        println!("{}", t);
+    }
+
+    #[test]
+    fn test_all_postings() {
+        let posting = |account: &str, num: &str| {
+            Posting::builder()
+                .units(
+                    IncompleteAmount::builder()
+                        .num(Some(Decimal::from_str_exact(num).unwrap()))
+                        .currency(Some(Currency::from("USD")))
+                        .build(),
+                )
+                .account(Account::from(account))
+                .build()
+        };
+        let first = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-02-03"))
+            .narration("Initial deposit".to_string())
+            .postings(vec![
+                posting("Assets:US:BofA:Checking", "100"),
+                posting("Assets:Cash", "-100"),
+            ])
+            .build();
+        let second = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-05"))
+            .narration("Lamb tagine with wine".to_string())
+            .postings(vec![
+                posting("Liabilities:CreditCard:CapitalOne", "-37.45"),
+                posting("Expenses:Restaurant", "20.00"),
+                posting("Expenses:Wine", "17.45"),
+            ])
+            .build();
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Transaction(first),
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-01-01"))
+                        .account(Account::from("Assets:Cash"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(second),
+            ])
+            .build();
+
+        let postings = ledger.all_postings();
+        assert_eq!(postings.len(), 5);
+        let dates: Vec<String> = postings.iter().map(|(d, _)| d.to_string()).collect();
+        assert_eq!(
+            dates,
+            vec![
+                "2014-02-03",
+                "2014-02-03",
+                "2014-05-05",
+                "2014-05-05",
+                "2014-05-05"
+            ]
+        );
+        assert_eq!(
+            postings[2].1.account,
+            Account::from("Liabilities:CreditCard:CapitalOne")
+        );
+    }
+
+    #[test]
+    fn test_commodity_meta() {
+        let mut meta = Meta::new();
+        meta.insert(
+            "name".to_string(),
+            MetaValue::Text("Hooli Corporation Class C Shares".to_string()),
+        );
+        meta.insert(
+            "asset-class".to_string(),
+            MetaValue::Text("stock".to_string()),
+        );
+        let hool = Commodity::builder()
+            .date(Date::from_str_unchecked("2012-01-01"))
+            .name(Currency::from("HOOL"))
+            .meta(meta)
+            .build();
+        let cad = Commodity::builder()
+            .date(Date::from_str_unchecked("1867-07-01"))
+            .name(Currency::from("CAD"))
+            .build();
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Commodity(cad),
+                directives::Directive::Commodity(hool),
+            ])
+            .build();
+
+        let meta = ledger.commodity_meta(&Currency::from("HOOL")).unwrap();
+        assert_eq!(
+            meta.get("name"),
+            Some(&MetaValue::Text(
+                "Hooli Corporation Class C Shares".to_string()
+            ))
+        );
+        assert!(ledger
+            .commodity_meta(&Currency::from("CAD"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(ledger.commodity_meta(&Currency::from("EUR")), None);
+    }
+
+    #[test]
+    fn test_commodity_precision() {
+        let commodity = |name: &str, precision: MetaValue| {
+            let mut meta = Meta::new();
+            meta.insert("precision".to_string(), precision);
+            directives::Directive::Commodity(
+                Commodity::builder()
+                    .date(Date::from_str_unchecked("2012-01-01"))
+                    .name(Currency::from(name))
+                    .meta(meta)
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                commodity("USD", MetaValue::Number(Decimal::from(2))),
+                commodity("HOOL", MetaValue::Text("high".to_string())),
+            ])
+            .build();
+
+        let precision = ledger.commodity_precision(&Currency::from("USD")).unwrap();
+        assert_eq!(precision, 2);
+        let amount = Amount::from((Decimal::new(133350, 4), "USD"));
+        assert_eq!(amount.with_scale(precision).to_string(), "13.34 USD");
+        assert_eq!(ledger.commodity_precision(&Currency::from("HOOL")), None);
+        assert_eq!(ledger.commodity_precision(&Currency::from("EUR")), None);
+    }
+
+    #[test]
+    fn test_undeclared_commodities() {
+        let units = |num: &str, currency: &str| {
+            IncompleteAmount::builder()
+                .num(Some(Decimal::from_str_exact(num).unwrap()))
+                .currency(Some(Currency::from(currency)))
+                .build()
+        };
+        let t = Transaction::builder()
+            .date(Date::from_str_unchecked("2012-11-03"))
+            .narration("Transfer to account in Canada".to_string())
+            .postings(vec![
+                Posting::builder()
+                    .account(Account::from("Assets:MyBank:Checking"))
+                    .units(units("-400.00", "USD"))
+                    .price(Some(units("1.09", "CAD")))
+                    .build(),
+                Posting::builder()
+                    .account(Account::from("Assets:FR:SocGen:Checking"))
+                    .units(units("436.01", "CAD"))
+                    .build(),
+            ])
+            .build();
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Commodity(
+                    Commodity::builder()
+                        .date(Date::from_str_unchecked("1867-07-01"))
+                        .name(Currency::from("CAD"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(t),
+            ])
+            .build();
+
+        let undeclared = ledger.undeclared_commodities();
+        assert_eq!(undeclared.len(), 1);
+        assert!(undeclared.contains("USD"));
+        assert!(!undeclared.contains("CAD"));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .account(Account::from("Assets:US:BofA:Checking"))
+                        .currencies(vec![Currency::from("USD")])
+                        .build(),
+                ),
+                directives::Directive::Transaction(
+                    Transaction::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .narration("Initial deposit".to_string())
+                        .postings(vec![Posting::builder()
+                            .account(Account::from("Assets:US:BofA:Checking"))
+                            .units(
+                                IncompleteAmount::builder()
+                                    .num(Some(Decimal::from(100)))
+                                    .currency(Some(Currency::from("USD")))
+                                    .build(),
+                            )
+                            .build()])
+                        .build(),
+                ),
+                directives::Directive::Unsupported(
+                    "2014-03-01 budget Expenses:Food monthly 400.00 USD".into(),
+                ),
+                directives::Directive::Note(
+                    Note::builder()
+                        .date(Date::from_str_unchecked("2014-04-10"))
+                        .account(Account::from("Assets:US:BofA:Checking"))
+                        .comment("Called to confirm wire transfer.".to_string())
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let mut buf: Vec<u8> = Vec::new();
+        ledger.write_to(&mut buf).unwrap();
+        let written = String::from_utf8(buf).unwrap();
+        assert_eq!(written, ledger.to_string());
+        assert_eq!(
+            written,
+            "2014-02-03 open Assets:US:BofA:Checking USD\n\
+             \n\
+             2014-02-03 * \"Initial deposit\"\n\
+             \x20 Assets:US:BofA:Checking\t100 USD\n\
+             \n\
+             2014-03-01 budget Expenses:Food monthly 400.00 USD\n\
+             \n\
+             2014-04-10 note Assets:US:BofA:Checking \"Called to confirm wire transfer.\"\n"
+        );
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let ledger = |option: &str, plugin: &str, include: &str| {
+            Ledger::builder()
+                .directives(vec![
+                    directives::Directive::Option(
+                        BcOption::builder()
+                            .name("title".to_string())
+                            .val("Ed's Personal Ledger".to_string())
+                            .source(Some(option.to_string().into()))
+                            .build(),
+                    ),
+                    directives::Directive::Plugin(
+                        Plugin::builder()
+                            .module("beancount.plugins.auto_accounts".to_string())
+                            .source(Some(plugin.to_string().into()))
+                            .build(),
+                    ),
+                    directives::Directive::Include(
+                        Include::builder()
+                            .filename("accounts.beancount".to_string())
+                            .source(Some(include.to_string().into()))
+                            .build(),
+                    ),
+                ])
+                .build()
+        };
+        let a = ledger(
+            r#"option "title" "Ed's Personal Ledger""#,
+            r#"plugin "beancount.plugins.auto_accounts""#,
+            r#"include "accounts.beancount""#,
+        );
+        let b = ledger(
+            r#"option  "title"   "Ed's Personal Ledger""#,
+            r#"plugin "beancount.plugins.auto_accounts"  ; comment"#,
+            r#"include	"accounts.beancount""#,
+        );
+        assert_ne!(a, b);
+        assert!(a.semantic_eq(&b));
+
+        let mut c = b.clone();
+        c.directives.pop();
+        assert!(!a.semantic_eq(&c));
+    }
+
+    #[test]
+    fn test_invalid_same_day_closes() {
+        let open = |date: &str, account: &str| {
+            directives::Directive::Open(
+                Open::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .account(Account::from(account))
+                    .build(),
+            )
+        };
+        let close = |date: &str, account: &str| {
+            directives::Directive::Close(
+                Close::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .account(Account::from(account))
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                open("2014-02-03", "Assets:US:BofA:Checking"),
+                open("2014-02-03", "Assets:US:BofA:Savings"),
+                close("2014-02-03", "Assets:US:BofA:Checking"),
+                close("2014-02-04", "Assets:US:BofA:Savings"),
+            ])
+            .build();
+
+        let invalid = ledger.invalid_same_day_closes();
+        assert_eq!(invalid.len(), 1);
+        let (open, close) = invalid[0];
+        assert_eq!(open.account, Account::from("Assets:US:BofA:Checking"));
+        assert_eq!(close.date, Date::from_str_unchecked("2014-02-03"));
+    }
+
+    #[test]
+    fn test_to_formatted_string() {
+        let units = |num: &str| {
+            IncompleteAmount::builder()
+                .num(Some(Decimal::from_str_exact(num).unwrap()))
+                .currency(Some(Currency::from("USD")))
+                .build()
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .account(Account::from("Assets:US:BofA:Checking"))
+                        .currencies(vec![Currency::from("USD")])
+                        .build(),
+                ),
+                directives::Directive::Transaction(
+                    Transaction::builder()
+                        .date(Date::from_str_unchecked("2014-05-05"))
+                        .payee(Some("Cafe Mogador".into()))
+                        .narration("Lamb tagine with wine".to_string())
+                        .postings(vec![
+                            Posting::builder()
+                                .account(Account::from("Liabilities:CreditCard:CapitalOne"))
+                                .units(units("-37.45"))
+                                .build(),
+                            Posting::builder()
+                                .account(Account::from("Expenses:Restaurant"))
+                                .units(units("37.45"))
+                                .build(),
+                        ])
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let expected = r#"2014-02-03 open Assets:US:BofA:Checking USD
+
+2014-05-05 * "Cafe Mogador" "Lamb tagine with wine"
+  Liabilities:CreditCard:CapitalOne         -37.45 USD
+  Expenses:Restaurant                        37.45 USD
+"#;
+        assert_eq!(ledger.to_formatted_string(50), expected);
+    }
+
+    #[test]
+    fn test_suspicious_balances() {
+        let balance = |account: &str| {
+            directives::Directive::Balance(
+                Balance::builder()
+                    .date(Date::from_str_unchecked("2014-12-26"))
+                    .account(Account::from(account))
+                    .amount(
+                        Amount::builder()
+                            .num(Decimal::from(100))
+                            .currency(Currency::from("USD"))
+                            .build(),
+                    )
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                balance("Assets:US:BofA:Checking"),
+                balance("Expenses:Restaurant"),
+            ])
+            .build();
+
+        let suspicious = ledger.suspicious_balances();
+        assert_eq!(suspicious.len(), 1);
+        assert_eq!(suspicious[0].account, Account::from("Expenses:Restaurant"));
+    }
+
+    #[test]
+    fn test_account_index() {
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .account(Account::from("Assets:US:BofA:Checking"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2014-02-04"),
+                    Flag::Okay,
+                    "Withdrawal".to_string(),
+                    Account::from("Assets:US:BofA:Checking"),
+                    Account::from("Assets:Cash"),
+                    Amount::from((Decimal::from(100), "USD")),
+                )),
+                directives::Directive::Note(
+                    Note::builder()
+                        .date(Date::from_str_unchecked("2014-04-10"))
+                        .account(Account::from("Assets:US:Vanguard:Cash"))
+                        .comment("Opened account.".to_string())
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let index = ledger.account_index();
+        assert_eq!(index.len(), 3);
+        assert!(index.contains(&Account::from("Assets:Cash")));
+        assert!(index.contains(&Account::from("Assets:US:BofA:Checking")));
+        assert!(!index.contains(&Account::from("Assets:US:BofA")));
+        assert_eq!(
+            index.children_of(&Account::from("Assets:US")),
+            vec![
+                &Account::from("Assets:US:BofA:Checking"),
+                &Account::from("Assets:US:Vanguard:Cash"),
+            ]
+        );
+        assert!(index.children_of(&Account::from("Assets:Cash")).is_empty());
+    }
+
+    #[test]
+    fn test_trial_balance() {
+        let transaction = |date: &str, from: &str, to: &str, amount: &str| {
+            directives::Directive::Transaction(Transaction::simple(
+                Date::from_str_unchecked(date),
+                Flag::Okay,
+                "Transfer".to_string(),
+                Account::from(from),
+                Account::from(to),
+                amount.parse().unwrap(),
+            ))
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                transaction("2014-01-05", "Income:Salary", "Assets:Checking", "1000 USD"),
+                transaction("2014-01-10", "Assets:Checking", "Assets:Wallet", "200 USD"),
+                transaction("2014-01-12", "Income:Salary", "Assets:Wallet", "50 EUR"),
+                transaction("2014-02-01", "Assets:Checking", "Assets:Wallet", "300 USD"),
+            ])
+            .build();
+
+        let balances = ledger.trial_balance(&Date::from_str_unchecked("2014-01-31"));
+        assert_eq!(balances.len(), 3);
+        let checking = &balances[&Account::from("Assets:Checking")];
+        assert_eq!(checking.units("USD"), Decimal::from(800));
+        assert_eq!(checking.positions().len(), 1);
+        let wallet = &balances[&Account::from("Assets:Wallet")];
+        assert_eq!(wallet.units("USD"), Decimal::from(200));
+        assert_eq!(wallet.units("EUR"), Decimal::from(50));
+        assert_eq!(wallet.positions().len(), 2);
+        let salary = &balances[&Account::from("Income:Salary")];
+        assert_eq!(salary.units("USD"), Decimal::from(-1000));
+        assert_eq!(salary.units("EUR"), Decimal::from(-50));
+    }
+
+    #[test]
+    fn test_trial_balance_fills_elided_posting() {
+        let ledger = Ledger::builder()
+            .directives(vec![directives::Directive::Transaction(
+                Transaction::builder()
+                    .date(Date::from_str_unchecked("2014-05-05"))
+                    .narration("Lunch")
+                    .postings(vec![
+                        posting("Expenses:Food", "37.45 USD", None),
+                        posting("Liabilities:CreditCard", "", None),
+                    ])
+                    .build(),
+            )])
+            .build();
+
+        let balances = ledger.trial_balance(&Date::from_str_unchecked("2014-05-31"));
+        assert_eq!(
+            balances[&Account::from("Liabilities:CreditCard")].to_string(),
+            "-37.45 USD"
+        );
+        let total: Decimal = balances.values().map(|i| i.units("USD")).sum();
+        assert!(total.is_zero());
+    }
+
+    #[test]
+    fn test_trial_balance_books_reductions() {
+        let trade = |date: &str, postings: Vec<Posting>| {
+            directives::Directive::Transaction(
+                Transaction::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .narration("Trade")
+                    .postings(postings)
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                trade(
+                    "2014-02-11",
+                    vec![
+                        posting("Assets:ETrade:IVV", "10 IVV", Some("{183.07 USD}")),
+                        posting("Assets:ETrade:Cash", "-1830.70 USD", None),
+                    ],
+                ),
+                trade(
+                    "2014-07-11",
+                    vec![
+                        posting("Assets:ETrade:IVV", "-10 IVV", Some("{183.07 USD}")),
+                        posting("Assets:ETrade:Cash", "1979.00 USD", None),
+                        posting("Income:PnL", "", None),
+                    ],
+                ),
+                trade(
+                    "2014-08-01",
+                    vec![
+                        posting("Assets:ETrade:IVV", "5 IVV", Some("{190.00 USD}")),
+                        posting("Assets:ETrade:Cash", "-950.00 USD", None),
+                    ],
+                ),
+                trade(
+                    "2014-09-02",
+                    vec![
+                        posting("Assets:ETrade:IVV", "-5 IVV", Some("{}")),
+                        posting("Assets:ETrade:Cash", "1000.00 USD", None),
+                        posting("Income:PnL", "", None),
+                    ],
+                ),
+            ])
+            .build();
+
+        let balances = ledger.trial_balance(&Date::from_str_unchecked("2014-07-31"));
+        assert!(balances[&Account::from("Assets:ETrade:IVV")].is_empty());
+        assert_eq!(
+            balances[&Account::from("Income:PnL")].to_string(),
+            "-148.30 USD"
+        );
+
+        let balances = ledger.trial_balance(&Date::from_str_unchecked("2014-09-30"));
+        assert!(balances[&Account::from("Assets:ETrade:IVV")].is_empty());
+        assert_eq!(
+            balances[&Account::from("Income:PnL")].to_string(),
+            "-198.30 USD"
+        );
+        let total: Decimal = balances.values().map(|i| i.units("USD")).sum();
+        assert!(total.is_zero());
+    }
+
+    #[test]
+    fn test_is_leaf_account() {
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .account(Account::from("Assets:US:BofA"))
+                        .build(),
+                ),
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .account(Account::from("Assets:US:BofA:Checking"))
+                        .build(),
+                ),
+            ])
+            .build();
+
+        assert!(!ledger.is_leaf_account(&Account::from("Assets:US:BofA")));
+        assert!(!ledger.is_leaf_account(&Account::from("Assets:US")));
+        assert!(ledger.is_leaf_account(&Account::from("Assets:US:BofA:Checking")));
+    }
+
+    #[test]
+    fn test_entries() {
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Option(
+                    BcOption::builder()
+                        .name("title".to_string())
+                        .val("Example".to_string())
+                        .build(),
+                ),
+                directives::Directive::Commodity(
+                    Commodity::builder()
+                        .date(Date::from_str_unchecked("1980-05-12"))
+                        .name("USD".to_string())
+                        .build(),
+                ),
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-02-03"))
+                        .account(Account::from("Assets:US:BofA:Checking"))
+                        .build(),
+                ),
+                directives::Directive::Unsupported("".into()),
+            ])
+            .build();
+
+        assert_eq!(ledger.entries().count(), 3);
+        let dates: Vec<&Date> = ledger.entries().filter_map(|e| e.date()).collect();
+        assert_eq!(
+            dates,
+            vec![
+                &Date::from_str_unchecked("1980-05-12"),
+                &Date::from_str_unchecked("2014-02-03"),
+            ]
+        );
+        assert_eq!(ledger.entries().filter(|e| e.meta().is_some()).count(), 1);
+    }
+
+    #[test]
+    fn test_root_names() {
+        let ledger = Ledger::builder()
+            .directives(vec![directives::Directive::Option(
+                BcOption::builder()
+                    .name("name_assets".to_string())
+                    .val("Activa".to_string())
+                    .build(),
+            )])
+            .build();
+
+        let names = ledger.root_names();
+        assert_eq!(names.assets, "Activa");
+        assert_eq!(names.expenses, "Expenses");
+        assert_eq!(Account::from("Assets:US").display_with(&names), "Activa:US");
+    }
+
+    #[test]
+    fn test_diff() {
+        let open = |date: &str, account: &str| {
+            directives::Directive::Open(
+                Open::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .account(Account::from(account))
+                    .build(),
+            )
+        };
+        let old = Ledger::builder()
+            .directives(vec![
+                open("2014-02-03", "Assets:US:BofA:Checking"),
+                open("2014-02-03", "Assets:Cash"),
+            ])
+            .build();
+        let new = Ledger::builder()
+            .directives(vec![
+                open("2014-02-03", "Assets:US:BofA:Checking"),
+                open("2014-02-03", "Liabilities:CreditCard"),
+            ])
+            .build();
+
+        let diff = old.diff(&new);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.common, vec![&old.directives[0]]);
+        assert_eq!(diff.removed, vec![&old.directives[1]]);
+        assert_eq!(diff.added, vec![&new.directives[1]]);
+        assert!(old.diff(&old.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_display_commodity_meta_and_document() {
+        let mut meta = Meta::new();
+        meta.insert(
+            "name".to_string(),
+            MetaValue::Text("Hooli Corporation Class C Shares".to_string()),
+        );
+        meta.insert(
+            "asset-class".to_string(),
+            MetaValue::Text("stock".to_string()),
+        );
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Commodity(
+                    Commodity::builder()
+                        .date(Date::from_str_unchecked("1867-07-01"))
+                        .name("CAD".to_string())
+                        .build(),
+                ),
+                directives::Directive::Commodity(
+                    Commodity::builder()
+                        .date(Date::from_str_unchecked("2012-01-01"))
+                        .name("HOOL".to_string())
+                        .meta(meta)
+                        .build(),
+                ),
+                directives::Directive::Document(
+                    Document::builder()
+                        .date(Date::from_str_unchecked("2013-11-03"))
+                        .account(Account::from("Liabilities:CreditCard"))
+                        .path("/home/joe/stmts/apr-2014.pdf".to_string())
+                        .build(),
+                ),
+            ])
+            .build();
+
+        assert_eq!(
+            ledger.to_string(),
+            r#"1867-07-01 commodity CAD
+
+2012-01-01 commodity HOOL
+  asset-class: "stock"
+  name: "Hooli Corporation Class C Shares"
+
+2013-11-03 document Liabilities:CreditCard "/home/joe/stmts/apr-2014.pdf"
+"#
+        );
+    }
+
+    #[test]
+    fn test_mark_transactions() {
+        let transaction = |payee: &str, flag: Flag| {
+            let mut t = lunch("2014-05-05");
+            t.flag = flag;
+            t.payee = Some(payee.to_string().into());
+            directives::Directive::Transaction(t)
+        };
+        let mut ledger = Ledger::builder()
+            .directives(vec![
+                transaction("Cafe Mogador", Flag::Warning),
+                transaction("Cafe Mogador", Flag::Warning),
+                transaction("Kin Soy", Flag::Warning),
+            ])
+            .build();
+
+        ledger.mark_transactions(
+            |t| t.payee.as_deref() == Some("Cafe Mogador") && t.flag == Flag::Warning,
+            Flag::Okay,
+        );
+
+        let flags: Vec<&Flag> = ledger
+            .directives
+            .iter()
+            .filter_map(|d| match d {
+                directives::Directive::Transaction(t) => Some(&t.flag),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(flags, vec![&Flag::Okay, &Flag::Okay, &Flag::Warning]);
+    }
+
+    #[test]
+    fn test_opening_balance() {
+        let checking = Account::from("Assets:US:BofA:Checking");
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2002-01-17"))
+                        .account(checking.clone())
+                        .build(),
+                ),
+                directives::Directive::Pad(
+                    Pad::builder()
+                        .date(Date::from_str_unchecked("2002-01-17"))
+                        .pad_to_account(checking.clone())
+                        .pad_from_account(Account::from("Equity:Opening-Balances"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2014-07-01"),
+                    Flag::Okay,
+                    "Withdrawal".to_string(),
+                    checking.clone(),
+                    Account::from("Assets:Cash"),
+                    Amount::from((Decimal::from(100), "USD")),
+                )),
+                directives::Directive::Balance(
+                    Balance::builder()
+                        .date(Date::from_str_unchecked("2014-07-09"))
+                        .account(checking.clone())
+                        .amount(Amount::from((Decimal::new(98734, 2), "USD")))
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let padding = ledger.resolve_pads();
+        assert_eq!(padding.len(), 1);
+        assert_eq!(
+            padding[0].to_string(),
+            "2002-01-17 P \"(Padding inserted for balance of 987.34 USD)\"
+  Assets:US:BofA:Checking\t1087.34 USD
+  Equity:Opening-Balances\t-1087.34 USD
+"
+        );
+        assert_eq!(
+            ledger.opening_balance(&checking),
+            Some(Amount::from((Decimal::new(108734, 2), "USD")))
+        );
+        assert_eq!(ledger.opening_balance(&Account::from("Assets:Cash")), None);
+    }
+
+    #[test]
+    fn test_opening_balance_from_opening_transaction() {
+        let checking = Account::from("Assets:US:BofA:Checking");
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2014-01-01"),
+                    Flag::Okay,
+                    "Opening balance".to_string(),
+                    Account::from("Equity:Opening-Balances"),
+                    checking.clone(),
+                    Amount::from((Decimal::new(320000, 2), "USD")),
+                )),
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2014-07-01"),
+                    Flag::Okay,
+                    "Withdrawal".to_string(),
+                    checking.clone(),
+                    Account::from("Assets:Cash"),
+                    Amount::from((Decimal::from(100), "USD")),
+                )),
+            ])
+            .build();
+
+        assert_eq!(
+            ledger.opening_balance(&checking),
+            Some(Amount::from((Decimal::new(320000, 2), "USD")))
+        );
+        assert_eq!(ledger.opening_balance(&Account::from("Assets:Cash")), None);
+    }
+
+    #[test]
+    fn test_opening_balance_with_elided_posting() {
+        let card = Account::from("Liabilities:CreditCard");
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Pad(
+                    Pad::builder()
+                        .date(Date::from_str_unchecked("2014-03-01"))
+                        .pad_to_account(card.clone())
+                        .pad_from_account(Account::from("Equity:Opening-Balances"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(
+                    Transaction::builder()
+                        .date(Date::from_str_unchecked("2014-03-05"))
+                        .narration("Lunch")
+                        .postings(vec![
+                            posting("Expenses:Food", "37.45 USD", None),
+                            posting("Liabilities:CreditCard", "", None),
+                        ])
+                        .build(),
+                ),
+                directives::Directive::Balance(
+                    Balance::builder()
+                        .date(Date::from_str_unchecked("2014-04-01"))
+                        .account(card.clone())
+                        .amount(Amount::from((Decimal::new(-3745, 2), "USD")))
+                        .build(),
+                ),
+            ])
+            .build();
+
+        assert!(ledger.resolve_pads().is_empty());
+        assert_eq!(ledger.opening_balance(&card), None);
+        assert!(ledger.opening_balances(&card).is_empty());
+    }
+
+    #[test]
+    fn test_opening_balance_in_several_currencies() {
+        let checking = Account::from("Assets:US:BofA:Checking");
+        let balance = |num: i64, currency: &str| {
+            directives::Directive::Balance(
+                Balance::builder()
+                    .date(Date::from_str_unchecked("2014-07-09"))
+                    .account(checking.clone())
+                    .amount(Amount::from((Decimal::new(num, 2), currency)))
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Pad(
+                    Pad::builder()
+                        .date(Date::from_str_unchecked("2002-01-17"))
+                        .pad_to_account(checking.clone())
+                        .pad_from_account(Account::from("Equity:Opening-Balances"))
+                        .build(),
+                ),
+                balance(98734, "USD"),
+                balance(25000, "EUR"),
+            ])
+            .build();
+
+        assert_eq!(
+            ledger.opening_balances(&checking),
+            BTreeMap::from([
+                ("EUR".to_string(), Decimal::new(25000, 2)),
+                ("USD".to_string(), Decimal::new(98734, 2)),
+            ])
+        );
+        assert_eq!(ledger.opening_balance(&checking), None);
+    }
+
+    #[test]
+    fn test_resolve_pads_negative_balance() {
+        let card = Account::from("Liabilities:CreditCard");
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Pad(
+                    Pad::builder()
+                        .date(Date::from_str_unchecked("2014-03-01"))
+                        .pad_to_account(card.clone())
+                        .pad_from_account(Account::from("Equity:Opening-Balances"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(lunch("2014-03-05")),
+                directives::Directive::Balance(
+                    Balance::builder()
+                        .date(Date::from_str_unchecked("2014-04-01"))
+                        .account(card.clone())
+                        .amount(Amount::from((Decimal::new(-64523, 2), "USD")))
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let padding = ledger.resolve_pads();
+        assert_eq!(padding.len(), 1);
+        assert_eq!(
+            padding[0].to_string(),
+            "2014-03-01 P \"(Padding inserted for balance of -645.23 USD)\"
+  Liabilities:CreditCard\t-607.78 USD
+  Equity:Opening-Balances\t607.78 USD
+"
+        );
+        assert_eq!(
+            ledger.opening_balance(&card),
+            Some(Amount::from((Decimal::new(-60778, 2), "USD")))
+        );
+    }
+
+    #[test]
+    fn test_intern_accounts() {
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Transaction(lunch("2014-05-05")),
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2014-05-12"),
+                    Flag::Okay,
+                    "Dinner".to_string(),
+                    Account::from("Liabilities:CreditCard"),
+                    Account::from("Expenses:Food:Restaurant"),
+                    Amount::from((Decimal::new(5210, 2), "USD")),
+                )),
+            ])
+            .build();
+
+        let mut interner = ledger.intern_accounts();
+        assert_eq!(interner.len(), 2);
+
+        let card = Account::from("Liabilities:CreditCard");
+        let id = interner.get(&card).unwrap();
+        assert_eq!(interner.intern(&card), id);
+        assert_ne!(
+            interner.get(&Account::from("Expenses:Food:Restaurant")),
+            Some(id)
+        );
+        assert_eq!(interner.resolve(id), &card);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_accept() {
+        #[derive(Default)]
+        struct Counter {
+            opens: usize,
+            transactions: usize,
+        }
+
+        impl DirectiveVisitor for Counter {
+            fn visit_open(&mut self, _: &Open) {
+                self.opens += 1;
+            }
+
+            fn visit_transaction(&mut self, _: &Transaction<'_>) {
+                self.transactions += 1;
+            }
+        }
+
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-05-01"))
+                        .account(Account::from("Liabilities:CreditCard"))
+                        .build(),
+                ),
+                directives::Directive::Transaction(lunch("2014-05-05")),
+                directives::Directive::Close(
+                    Close::builder()
+                        .date(Date::from_str_unchecked("2016-11-28"))
+                        .account(Account::from("Liabilities:CreditCard"))
+                        .build(),
+                ),
+                directives::Directive::Unsupported("".into()),
+            ])
+            .build();
+
+        let mut counter = Counter::default();
+        ledger.accept(&mut counter);
+        assert_eq!(counter.opens, 1);
+        assert_eq!(counter.transactions, 1);
+    }
+
+    #[test]
+    fn test_accept_mut() {
+        struct UppercaseNarrations;
+
+        impl DirectiveVisitorMut for UppercaseNarrations {
+            fn visit_transaction(&mut self, transaction: &mut Transaction<'_>) {
+                transaction.narration = transaction.narration.to_uppercase().into();
+            }
+        }
+
+        let transaction = |narration: &str| {
+            let mut t = lunch("2014-05-05");
+            t.narration = narration.to_string().into();
+            directives::Directive::Transaction(t)
+        };
+        let mut ledger = Ledger::builder()
+            .directives(vec![transaction("Lunch"), transaction("Dinner with Kin")])
+            .build();
+
+        ledger.accept_mut(&mut UppercaseNarrations);
+
+        let narrations: Vec<&str> = ledger
+            .transactions()
+            .map(|t| t.narration.as_ref())
+            .collect();
+        assert_eq!(narrations, vec!["LUNCH", "DINNER WITH KIN"]);
+    }
+
+    #[test]
+    fn test_validate_currency_constraints() {
+        let card = Account::from("Liabilities:CreditCard");
+        let lunch = |currency: &str| {
+            directives::Directive::Transaction(Transaction::simple(
+                Date::from_str_unchecked("2014-05-05"),
+                Flag::Okay,
+                "Lunch in Paris".to_string(),
+                card.clone(),
+                Account::from("Expenses:Food:Restaurant"),
+                Amount::from((Decimal::new(3745, 2), currency)),
+            ))
+        };
+        let mut ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-05-01"))
+                        .account(card.clone())
+                        .currencies(vec![Currency::from("USD")])
+                        .build(),
+                ),
+                directives::Directive::Open(
+                    Open::builder()
+                        .date(Date::from_str_unchecked("2014-05-01"))
+                        .account(Account::from("Expenses:Food:Restaurant"))
+                        .build(),
+                ),
+                lunch("USD"),
+            ])
+            .build();
+        assert_eq!(ledger.validate_currency_constraints(), Ok(()));
+
+        ledger.directives.push(lunch("EUR"));
+        let errors = ledger.validate_currency_constraints().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![CurrencyConstraintError {
+                account: card,
+                used_currency: Currency::from("EUR"),
+                allowed: vec![Currency::from("USD")],
+                date: Date::from_str_unchecked("2014-05-05"),
+                narration: "Lunch in Paris".to_string(),
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "2014-05-05 \"Lunch in Paris\": Liabilities:CreditCard does not allow EUR (allowed: USD)"
+        );
+    }
+
+    #[test]
+    fn test_apply_pushed_meta() {
+        let project = MetaValue::Text("X".to_string());
+        let mut ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::PushMeta(
+                    PushMeta::builder()
+                        .key("project".to_string())
+                        .value(project.clone())
+                        .build(),
+                ),
+                directives::Directive::Transaction(lunch("2014-05-05")),
+                directives::Directive::Transaction(lunch("2014-05-06")),
+                directives::Directive::PopMeta(
+                    PopMeta::builder().key("project".to_string()).build(),
+                ),
+                directives::Directive::Transaction(lunch("2014-05-07")),
+            ])
+            .build();
+
+        ledger.apply_pushed_meta();
+
+        let transactions: Vec<&Transaction> = ledger.transactions().collect();
+        for t in &transactions[..2] {
+            assert_eq!(t.meta.get("project"), Some(&project));
+            for posting in &t.postings {
+                assert_eq!(posting.meta.get("project"), Some(&project));
+            }
+        }
+        assert!(transactions[2].meta.is_empty());
+        assert!(transactions[2].postings.iter().all(|p| p.meta.is_empty()));
+    }
+
+    #[test]
+    fn test_generate_missing_opens() {
+        let transaction = |date: &str, from: &str, to: &str| {
+            directives::Directive::Transaction(Transaction::simple(
+                Date::from_str_unchecked(date),
+                Flag::Okay,
+                "Transfer".to_string(),
+                Account::from(from),
+                Account::from(to),
+                Amount::from((Decimal::from(100), "USD")),
+            ))
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                transaction("2014-05-05", "Liabilities:CreditCard", "Expenses:Food"),
+                transaction("2013-12-31", "Assets:Checking", "Liabilities:CreditCard"),
+            ])
+            .build();
+
+        let opens = ledger.generate_missing_opens(&Date::from_str_unchecked("2014-01-01"));
+        let open = |date: &str, account: &str| {
+            Open::builder()
+                .date(Date::from_str_unchecked(date))
+                .account(Account::from(account))
+                .build()
+        };
+        assert_eq!(
+            opens,
+            vec![
+                open("2013-12-31", "Assets:Checking"),
+                open("2013-12-31", "Liabilities:CreditCard"),
+                open("2014-01-01", "Expenses:Food"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_usage_date() {
+        let ledger = Ledger::builder()
+            .directives(vec![
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2015-01-02"),
+                    Flag::Okay,
+                    "Dinner".to_string(),
+                    Account::from("Liabilities:CreditCard"),
+                    Account::from("Expenses:Food:Restaurant"),
+                    Amount::from((Decimal::new(5210, 2), "USD")),
+                )),
+                directives::Directive::Transaction(lunch("2014-05-05")),
+            ])
+            .build();
+
+        assert_eq!(
+            ledger.first_usage_date(&Account::from("Expenses:Food:Restaurant")),
+            Some(Date::from_str_unchecked("2014-05-05"))
+        );
+        assert_eq!(
+            ledger.first_usage_date(&Account::from("Expenses:Food")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let open = |account: &str| {
+            directives::Directive::Open(
+                Open::builder()
+                    .date(Date::from_str_unchecked("2014-05-01"))
+                    .account(Account::from(account))
+                    .build(),
+            )
+        };
+        let mut ledger = Ledger::builder()
+            .directives(vec![open("Assets:Cash"), open("Liabilities:CreditCard")])
+            .build();
+
+        let mut accounts = Vec::new();
+        for directive in &ledger {
+            if let directives::Directive::Open(o) = directive {
+                accounts.push(o.account.to_string());
+            }
+        }
+        assert_eq!(accounts, vec!["Assets:Cash", "Liabilities:CreditCard"]);
+
+        for directive in &mut ledger {
+            if let directives::Directive::Open(o) = directive {
+                o.currencies.push(Currency::from("USD"));
+            }
+        }
+        let directives: Vec<directives::Directive> = ledger.into_iter().collect();
+        assert!(directives.iter().all(|d| match d {
+            directives::Directive::Open(o) => o.currencies == vec![Currency::from("USD")],
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn test_rolled_up_balances() {
+        let transfer = |date: &str, from: &str, to: &str, num: Decimal| {
+            directives::Directive::Transaction(Transaction::simple(
+                Date::from_str_unchecked(date),
+                Flag::Okay,
+                "Transfer".to_string(),
+                Account::from(from),
+                Account::from(to),
+                Amount::from((num, "USD")),
+            ))
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                transfer(
+                    "2014-01-05",
+                    "Income:US:Salary",
+                    "Assets:US:BofA:Checking",
+                    Decimal::new(300000, 2),
+                ),
+                transfer(
+                    "2014-01-10",
+                    "Assets:US:BofA:Checking",
+                    "Assets:US:Vanguard:Cash",
+                    Decimal::new(50000, 2),
+                ),
+                transfer(
+                    "2014-02-01",
+                    "Assets:US:BofA:Checking",
+                    "Expenses:Rent",
+                    Decimal::new(150000, 2),
+                ),
+                directives::Directive::Transaction(
+                    Transaction::builder()
+                        .date(Date::from_str_unchecked("2014-01-20"))
+                        .narration("Lunch")
+                        .postings(vec![
+                            posting("Expenses:Food:Restaurant", "37.45 USD", None),
+                            posting("Liabilities:US:CreditCard", "", None),
+                        ])
+                        .build(),
+                ),
+            ])
+            .build();
+
+        let balances = ledger.rolled_up_balances(&Date::from_str_unchecked("2014-01-31"));
+        let usd = |account: &str| balances[&Account::from(account)]["USD"];
+        assert_eq!(usd("Assets:US:BofA:Checking"), Decimal::new(250000, 2));
+        assert_eq!(usd("Assets:US:Vanguard:Cash"), Decimal::new(50000, 2));
+        assert_eq!(
+            usd("Assets:US"),
+            usd("Assets:US:BofA:Checking") + usd("Assets:US:Vanguard:Cash")
+        );
+        assert_eq!(usd("Assets"), Decimal::new(300000, 2));
+        assert_eq!(usd("Liabilities:US"), Decimal::new(-3745, 2));
+        assert_eq!(usd("Liabilities"), Decimal::new(-3745, 2));
+        assert_eq!(usd("Expenses"), Decimal::new(3745, 2));
+        assert!(!balances.contains_key(&Account::from("Expenses:Rent")));
+    }
+
+    #[test]
+    fn test_balance_gaps() {
+        let checking = Account::from("Assets:US:BofA:Checking");
+        let balance = |date: &str, num: i64| {
+            directives::Directive::Balance(
+                Balance::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .account(checking.clone())
+                    .amount(Amount::from((Decimal::new(num, 2), "USD")))
+                    .build(),
+            )
+        };
+        let withdrawal = |date: &str, num: i64| {
+            directives::Directive::Transaction(Transaction::simple(
+                Date::from_str_unchecked(date),
+                Flag::Okay,
+                "Withdrawal".to_string(),
+                checking.clone(),
+                Account::from("Assets:Cash"),
+                Amount::from((Decimal::new(num, 2), "USD")),
+            ))
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                balance("2014-07-01", 100000),
+                withdrawal("2014-07-01", 10000),
+                balance("2014-08-01", 90000),
+                withdrawal("2014-08-05", 10000),
+                withdrawal("2014-09-01", 5000),
+                balance("2014-09-01", 75000),
+                directives::Directive::Transaction(
+                    Transaction::builder()
+                        .date(Date::from_str_unchecked("2014-09-15"))
+                        .narration("Wire fee")
+                        .postings(vec![
+                            posting("Expenses:Bank:Fees", "50.00 USD", None),
+                            posting("Assets:US:BofA:Checking", "", None),
+                        ])
+                        .build(),
+                ),
+                balance("2014-10-01", 65000),
+            ])
+            .build();
+
+        let gaps = ledger.balance_gaps(&checking);
+        assert_eq!(gaps.len(), 3);
+        assert!(!gaps[0].is_mismatch());
+        assert_eq!(gaps[1].from.date, Date::from_str_unchecked("2014-08-01"));
+        assert_eq!(gaps[1].asserted_delta, Decimal::new(-15000, 2));
+        assert_eq!(gaps[1].transaction_delta, Decimal::new(-10000, 2));
+        assert!(gaps[1].is_mismatch());
+        assert_eq!(gaps[2].transaction_delta, Decimal::new(-10000, 2));
+        assert!(!gaps[2].is_mismatch());
+        assert!(ledger
+            .balance_gaps(&Account::from("Assets:Cash"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_implicit_opening_assertions() {
+        let open = |account: &str, currencies: Vec<Currency>| {
+            directives::Directive::Open(
+                Open::builder()
+                    .date(Date::from_str_unchecked("2014-05-01"))
+                    .account(Account::from(account))
+                    .currencies(currencies)
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                open("Liabilities:CreditCard", vec!["USD".to_string()]),
+                open("Assets:Cash", vec![]),
+                open("Equity:Opening-Balances", vec![]),
+                directives::Directive::Transaction(Transaction::simple(
+                    Date::from_str_unchecked("2014-04-30"),
+                    Flag::Okay,
+                    "Withdrawal".to_string(),
+                    Account::from("Liabilities:CreditCard"),
+                    Account::from("Assets:Cash"),
+                    Amount::from((Decimal::from(20), "EUR")),
+                )),
+            ])
+            .build();
+
+        let balance = |account: &str, currency: &str| {
+            Balance::builder()
+                .date(Date::from_str_unchecked("2014-05-01"))
+                .account(Account::from(account))
+                .amount(Amount::from((Decimal::ZERO, currency)))
+                .build()
+        };
+        assert_eq!(
+            ledger.implicit_opening_assertions(),
+            vec![
+                balance("Liabilities:CreditCard", "USD"),
+                balance("Assets:Cash", "EUR"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_alias() {
+        let mut ledger = Ledger::default();
+        assert_eq!(ledger.resolve_alias("cc"), None);
+
+        ledger.set_aliases(HashMap::from([(
+            "cc".to_string(),
+            Account::from("Liabilities:CreditCard"),
+        )]));
+        assert_eq!(
+            ledger.resolve_alias("cc"),
+            Some(&Account::from("Liabilities:CreditCard"))
+        );
+        assert_eq!(ledger.resolve_alias("Liabilities:CreditCard"), None);
+    }
+
+    #[test]
+    fn test_holdings() {
+        let purchase = |date: &str, units: &str, cost: &str, cash: &str| {
+            directives::Directive::Transaction(
+                Transaction::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .narration("Bought shares of S&P 500")
+                    .postings(vec![
+                        Posting::builder()
+                            .account(Account::from("Assets:ETrade:IVV"))
+                            .units(units.parse::<Amount>().unwrap().into())
+                            .cost(Some(cost.parse().unwrap()))
+                            .build(),
+                        Posting::from_amount(
+                            Account::from("Assets:ETrade:Cash"),
+                            cash.parse().unwrap(),
+                        ),
+                    ])
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                purchase("2014-03-22", "15 IVV", "{187.12 USD}", "-2806.80 USD"),
+                purchase("2014-02-11", "20 IVV", "{183.07 USD}", "-3661.40 USD"),
+                purchase("2014-08-01", "5 IVV", "{190.00 USD}", "-950.00 USD"),
+            ])
+            .build();
+
+        let holdings = ledger.holdings(&Date::from_str_unchecked("2014-07-01"));
+        assert_eq!(holdings.keys().collect::<Vec<_>>(), vec!["IVV"]);
+        let lots: Vec<String> = holdings["IVV"].iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            lots,
+            vec![
+                "20 IVV {183.07 USD, 2014-02-11}",
+                "15 IVV {187.12 USD, 2014-03-22}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_holdings_after_sale() {
+        let trade = |date: &str, postings: Vec<Posting>| {
+            directives::Directive::Transaction(
+                Transaction::builder()
+                    .date(Date::from_str_unchecked(date))
+                    .narration("Trade")
+                    .postings(postings)
+                    .build(),
+            )
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                trade(
+                    "2014-02-11",
+                    vec![
+                        posting("Assets:ETrade:IVV", "10 IVV", Some("{183.07 USD}")),
+                        posting("Assets:ETrade:Cash", "", None),
+                    ],
+                ),
+                trade(
+                    "2014-03-22",
+                    vec![
+                        posting("Assets:ETrade:IVV", "15 IVV", Some("{187.12 USD}")),
+                        posting("Assets:ETrade:Cash", "", None),
+                    ],
+                ),
+                trade(
+                    "2014-07-11",
+                    vec![
+                        posting("Assets:ETrade:IVV", "-10 IVV", Some("{183.07 USD}")),
+                        posting("Assets:ETrade:Cash", "1979.00 USD", None),
+                        posting("Income:PnL", "", None),
+                    ],
+                ),
+                trade(
+                    "2014-08-01",
+                    vec![
+                        posting("Assets:ETrade:IVV", "-5 IVV", Some("{}")),
+                        posting("Assets:ETrade:Cash", "1000.00 USD", None),
+                        posting("Income:PnL", "", None),
+                    ],
+                ),
+            ])
+            .build();
+
+        let holdings = ledger.holdings(&Date::from_str_unchecked("2014-07-31"));
+        let lots: Vec<String> = holdings["IVV"].iter().map(|p| p.to_string()).collect();
+        assert_eq!(lots, vec!["15 IVV {187.12 USD, 2014-03-22}"]);
+
+        let holdings = ledger.holdings(&Date::from_str_unchecked("2014-08-31"));
+        let lots: Vec<String> = holdings["IVV"].iter().map(|p| p.to_string()).collect();
+        assert_eq!(lots, vec!["10 IVV {187.12 USD, 2014-03-22}"]);
+    }
+
+    #[test]
+    fn test_future_dated() {
+        let transaction = |date: &str| directives::Directive::Transaction(lunch(date));
+        let ledger = Ledger::builder()
+            .directives(vec![
+                transaction("2014-05-05"),
+                transaction("2014-06-01"),
+                directives::Directive::Include(
+                    Include::builder().filename("accounts.beancount").build(),
+                ),
+            ])
+            .build();
+
+        let today = Date::from_str_unchecked("2014-05-20");
+        assert_eq!(
+            ledger.future_dated(&today),
+            vec![&transaction("2014-06-01")]
+        );
+        assert!(ledger
+            .future_dated(&Date::from_str_unchecked("2014-06-01"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_postings_to_csv() {
+        let mut transaction = lunch("2014-05-05");
+        transaction.narration = "Lunch, \"Lamb tagine\"".into();
+        transaction.payee = Some("Cafe Mogador".into());
+        let ledger = Ledger::builder()
+            .directives(vec![directives::Directive::Transaction(transaction)])
+            .build();
+
+        let mut buffer = Vec::new();
+        ledger.postings_to_csv(&mut buffer).unwrap();
+        let csv = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "date,flag,payee,narration,account,amount,currency,cost,price",
+                r#"2014-05-05,*,Cafe Mogador,"Lunch, ""Lamb tagine""",Liabilities:CreditCard,-37.45,USD,,"#,
+                r#"2014-05-05,*,Cafe Mogador,"Lunch, ""Lamb tagine""",Expenses:Food:Restaurant,37.45,USD,,"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_len() {
+        let mut ledger = Ledger::default();
+        assert_eq!(ledger.len(), 0);
+        assert!(ledger.is_empty());
+
+        ledger.directives.push(directives::Directive::Open(Open::on(
+            Date::from_str_unchecked("2014-05-01"),
+            Account::from("Liabilities:CreditCard"),
+        )));
+        assert_eq!(ledger.len(), 1);
+        assert!(!ledger.is_empty());
+    }
+
+    #[test]
+    fn test_payees_and_narrations() {
+        let transaction = |payee: Option<&str>, narration: &str| {
+            let mut transaction = lunch("2014-05-05");
+            transaction.narration = narration.to_string().into();
+            transaction.payee = payee.map(|p| p.to_string().into());
+            directives::Directive::Transaction(transaction)
+        };
+        let ledger = Ledger::builder()
+            .directives(vec![
+                transaction(Some("Cafe Mogador"), "Lamb tagine"),
+                transaction(Some("Cafe Mogador"), "Couscous"),
+                transaction(Some(" "), ""),
+                transaction(Some("Chichipotle"), "Lamb tagine"),
+            ])
+            .build();
+
+        assert_eq!(
+            ledger.payees(),
+            BTreeSet::from(["Cafe Mogador".to_string(), "Chichipotle".to_string()])
+        );
+        assert_eq!(
+            ledger.narrations(),
+            BTreeSet::from(["Couscous".to_string(), "Lamb tagine".to_string()])
+        );
     }
 }
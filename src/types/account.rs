@@ -1,9 +1,16 @@
-use core::fmt;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{cmp, error, fmt, str::FromStr};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
 
 use typed_builder::TypedBuilder;
 
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.17ry42rqbuiu>
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AccountType {
     Assets,
     Liabilities,
@@ -23,6 +30,87 @@ impl AccountType {
             Expenses => "Expenses",
         }
     }
+
+    /// Position of the account type in the canonical Assets, Liabilities, Equity, Income,
+    /// Expenses order, from 0 to 4.
+    pub fn ordinal(&self) -> u8 {
+        use AccountType::*;
+        match self {
+            Assets => 0,
+            Liabilities => 1,
+            Equity => 2,
+            Income => 3,
+            Expenses => 4,
+        }
+    }
+
+    /// Renders the account type with the root names in `names`, e.g. `Activa` instead of
+    /// `Assets`.
+    pub fn display_with(&self, names: &RootNames) -> String {
+        names.name(*self).to_string()
+    }
+}
+
+/// The names of the five root accounts, which a ledger can rename with options such as
+/// `option "name_assets" "Activa"`. Defaults to the English names.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RootNames {
+    pub assets: String,
+    pub liabilities: String,
+    pub equity: String,
+    pub income: String,
+    pub expenses: String,
+}
+
+impl RootNames {
+    /// Returns the root name used for `account_type`.
+    pub fn name(&self, account_type: AccountType) -> &str {
+        use AccountType::*;
+        match account_type {
+            Assets => &self.assets,
+            Liabilities => &self.liabilities,
+            Equity => &self.equity,
+            Income => &self.income,
+            Expenses => &self.expenses,
+        }
+    }
+
+    /// Renames the root account of `account_type`.
+    pub fn set(&mut self, account_type: AccountType, name: String) {
+        use AccountType::*;
+        match account_type {
+            Assets => self.assets = name,
+            Liabilities => self.liabilities = name,
+            Equity => self.equity = name,
+            Income => self.income = name,
+            Expenses => self.expenses = name,
+        }
+    }
+}
+
+impl Default for RootNames {
+    fn default() -> Self {
+        use AccountType::*;
+        RootNames {
+            assets: Assets.default_name().to_string(),
+            liabilities: Liabilities.default_name().to_string(),
+            equity: Equity.default_name().to_string(),
+            income: Income.default_name().to_string(),
+            expenses: Expenses.default_name().to_string(),
+        }
+    }
+}
+
+impl cmp::Ord for AccountType {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.ordinal().cmp(&other.ordinal())
+    }
+}
+
+impl cmp::PartialOrd for AccountType {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl From<&str> for AccountType {
@@ -38,6 +126,22 @@ impl From<&str> for AccountType {
     }
 }
 
+/// Parses one of the five root account names, unlike `From<&str>` without panicking.
+impl FromStr for AccountType {
+    type Err = AccountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Assets" => Ok(AccountType::Assets),
+            "Liabilities" => Ok(AccountType::Liabilities),
+            "Equity" => Ok(AccountType::Equity),
+            "Income" => Ok(AccountType::Income),
+            "Expenses" => Ok(AccountType::Expenses),
+            _ => Err(AccountError::UnknownType(s.to_string())),
+        }
+    }
+}
+
 // account type to String
 impl fmt::Display for AccountType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -97,6 +201,7 @@ impl fmt::Display for AccountType {
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.17ry42rqbuiu>
 #[derive(Clone, Debug, Eq, PartialEq, Hash, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Account {
     /// Type of the account.
     pub account_type: AccountType,
@@ -117,6 +222,126 @@ impl From<&str> for Account {
     }
 }
 
+/// Parses an account such as `Assets:US:BofA:Checking`, rejecting unknown root names and
+/// empty components instead of panicking like `From<&str>`.
+impl FromStr for Account {
+    type Err = AccountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let account_type = parts.next().unwrap_or_default().parse()?;
+        let parts: Vec<String> = parts.map(|part| part.to_string()).collect();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(AccountError::EmptyComponent(s.to_string()));
+        }
+        Ok(Account {
+            account_type,
+            parts,
+        })
+    }
+}
+
+/// Error returned when a string cannot be parsed as an `Account`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AccountError {
+    /// The account does not start with one of the five root names.
+    UnknownType(String),
+    /// The account has an empty component, e.g. `Assets::Cash`.
+    EmptyComponent(String),
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccountError::UnknownType(s) => write!(f, "unknown account type: {}", s),
+            AccountError::EmptyComponent(s) => write!(f, "empty component in account: {}", s),
+        }
+    }
+}
+
+impl error::Error for AccountError {}
+
+impl Account {
+    /// Builds an account from its type and the parts following it. The parts are not checked
+    /// against beancount's naming rules.
+    pub fn from_parts_unchecked(account_type: AccountType, parts: Vec<String>) -> Account {
+        Account {
+            account_type,
+            parts,
+        }
+    }
+
+    /// Returns the account `leaf` nested under `parent`, e.g. `Assets:US:BofA:Checking` for
+    /// `Assets:US:BofA` and `Checking`. The leaf may itself contain several colon-separated
+    /// parts.
+    pub fn join(parent: &Account, leaf: &str) -> Account {
+        let mut parts = parent.parts.clone();
+        parts.extend(leaf.split(':').map(|s| s.to_string()));
+        Account::from_parts_unchecked(parent.account_type, parts)
+    }
+
+    /// Returns true if `other` is nested under this account, e.g. `Assets:US` is an ancestor of
+    /// `Assets:US:BofA:Checking`. An account is not its own ancestor.
+    pub fn is_ancestor_of(&self, other: &Account) -> bool {
+        self.account_type == other.account_type
+            && self.parts.len() < other.parts.len()
+            && other.parts.starts_with(&self.parts)
+    }
+
+    /// Returns true if the account matches `pattern`, a colon-separated account name in which
+    /// `*` stands for any single component and `**` for any number of components, including
+    /// none. For example, `Assets:*:Checking` matches `Assets:BofA:Checking`, and `Assets:**`
+    /// matches `Assets` and every account under it. The root is matched by its English name.
+    pub fn matches_glob(&self, pattern: &str) -> bool {
+        fn matches(pattern: &[&str], components: &[&str]) -> bool {
+            match (pattern.split_first(), components.split_first()) {
+                (None, None) => true,
+                (Some((&"**", rest)), _) => {
+                    matches(rest, components)
+                        || (!components.is_empty() && matches(pattern, &components[1..]))
+                }
+                (Some((&p, rest)), Some((&c, components))) => {
+                    (p == "*" || p == c) && matches(rest, components)
+                }
+                _ => false,
+            }
+        }
+
+        let mut components = Vec::with_capacity(self.parts.len() + 1);
+        components.push(self.account_type.default_name());
+        components.extend(self.parts.iter().map(String::as_str));
+        let pattern: Vec<&str> = pattern.split(':').collect();
+        matches(&pattern, &components)
+    }
+
+    /// Renders the account with the root names in `names`, e.g. `Activa:US` for `Assets:US`
+    /// when assets are renamed. `Display` always uses the English names.
+    pub fn display_with(&self, names: &RootNames) -> String {
+        let mut res = self.account_type.display_with(names);
+        for part in &self.parts {
+            res.push(':');
+            res.push_str(part);
+        }
+        res
+    }
+}
+
+/// Accounts sort by account type in the canonical Assets, Liabilities, Equity, Income, Expenses
+/// order, then lexicographically by their parts. This is the order of a chart of accounts.
+impl cmp::Ord for Account {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.account_type
+            .cmp(&other.account_type)
+            .then_with(|| self.parts.cmp(&other.parts))
+    }
+}
+
+impl cmp::PartialOrd for Account {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl fmt::Display for Account {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.parts.is_empty() {
@@ -127,6 +352,103 @@ impl fmt::Display for Account {
     }
 }
 
+/// A set of accounts built once for fast membership and hierarchy queries, e.g. with
+/// `Ledger::account_index`.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountIndex {
+    accounts: HashSet<Account>,
+}
+
+#[cfg(feature = "std")]
+impl AccountIndex {
+    pub fn contains(&self, account: &Account) -> bool {
+        self.accounts.contains(account)
+    }
+
+    /// Returns the indexed accounts nested under `parent` at any depth, in chart-of-accounts
+    /// order. Intermediate accounts that were never referenced are not included.
+    pub fn children_of(&self, parent: &Account) -> Vec<&Account> {
+        let mut children: Vec<&Account> = self
+            .accounts
+            .iter()
+            .filter(|a| parent.is_ancestor_of(a))
+            .collect();
+        children.sort();
+        children
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromIterator<Account> for AccountIndex {
+    fn from_iter<I: IntoIterator<Item = Account>>(accounts: I) -> Self {
+        AccountIndex {
+            accounts: accounts.into_iter().collect(),
+        }
+    }
+}
+
+/// A handle to an account stored once in an [AccountInterner]. Handles of the same interner
+/// are equal exactly when their accounts are, and are cheap to copy, compare and hash.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct InternedAccount(usize);
+
+/// Stores each distinct account once and hands out [InternedAccount] handles for it, so that
+/// large ledgers need not keep thousands of copies of the same account name.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct AccountInterner {
+    accounts: Vec<Account>,
+    ids: HashMap<Account, InternedAccount>,
+}
+
+#[cfg(feature = "std")]
+impl AccountInterner {
+    pub fn new() -> AccountInterner {
+        AccountInterner::default()
+    }
+
+    /// Returns the handle of `account`, storing it first if it is new to the interner.
+    pub fn intern(&mut self, account: &Account) -> InternedAccount {
+        if let Some(id) = self.ids.get(account) {
+            return *id;
+        }
+        let id = InternedAccount(self.accounts.len());
+        self.accounts.push(account.clone());
+        self.ids.insert(account.clone(), id);
+        id
+    }
+
+    /// Returns the handle of `account` if it was interned.
+    pub fn get(&self, account: &Account) -> Option<InternedAccount> {
+        self.ids.get(account).copied()
+    }
+
+    /// Returns the account behind `id`.
+    ///
+    /// Panics if `id` was handed out by another interner with more accounts.
+    pub fn resolve(&self, id: InternedAccount) -> &Account {
+        &self.accounts[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,6 +462,32 @@ mod tests {
         assert_eq!(AccountType::Expenses.default_name(), "Expenses");
     }
 
+    #[test]
+    fn test_account_type_ordinal() {
+        assert_eq!(AccountType::Assets.ordinal(), 0);
+        assert_eq!(AccountType::Expenses.ordinal(), 4);
+        assert!(AccountType::Assets < AccountType::Liabilities);
+        assert!(AccountType::Liabilities < AccountType::Equity);
+        assert!(AccountType::Equity < AccountType::Income);
+        assert!(AccountType::Income < AccountType::Expenses);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            "Assets:US:BofA:Checking".parse::<Account>(),
+            Ok(Account::from("Assets:US:BofA:Checking"))
+        );
+        assert_eq!(
+            "Asset:Cash".parse::<Account>(),
+            Err(AccountError::UnknownType("Asset".to_string()))
+        );
+        assert_eq!(
+            "Assets::Cash".parse::<Account>(),
+            Err(AccountError::EmptyComponent("Assets::Cash".to_string()))
+        );
+    }
+
     #[test]
     fn test_new_account() {
         let account = Account::builder()
@@ -185,4 +533,93 @@ mod tests {
         let account = Account::builder().account_type(AccountType::Assets).build();
         assert_eq!(account.to_string(), "Assets");
     }
+
+    #[test]
+    fn test_account_ordering() {
+        let mut accounts: Vec<Account> = vec![
+            "Expenses:Food:Groceries",
+            "Assets:US:BofA:Savings",
+            "Income:US:Acme:Salary",
+            "Liabilities:CA:RBC:CreditCard",
+            "Assets:US:BofA:Checking",
+            "Equity:Retained-Earnings",
+            "Assets:Receivables",
+            "Assets:US",
+        ]
+        .into_iter()
+        .map(Account::from)
+        .collect();
+        accounts.sort();
+
+        let sorted: Vec<String> = accounts.iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "Assets:Receivables",
+                "Assets:US",
+                "Assets:US:BofA:Checking",
+                "Assets:US:BofA:Savings",
+                "Liabilities:CA:RBC:CreditCard",
+                "Equity:Retained-Earnings",
+                "Income:US:Acme:Salary",
+                "Expenses:Food:Groceries",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_ancestor_of() {
+        let us = Account::from("Assets:US");
+        assert!(us.is_ancestor_of(&Account::from("Assets:US:BofA:Checking")));
+        assert!(Account::from("Assets").is_ancestor_of(&us));
+        assert!(!us.is_ancestor_of(&us));
+        assert!(!us.is_ancestor_of(&Account::from("Assets:USA")));
+        assert!(!us.is_ancestor_of(&Account::from("Liabilities:US:Chase")));
+    }
+
+    #[test]
+    fn test_matches_glob() {
+        let checking = Account::from("Assets:US:BofA:Checking");
+        assert!(checking.matches_glob("Assets:*:*:Checking"));
+        assert!(checking.matches_glob("Assets:US:BofA:Checking"));
+        assert!(!checking.matches_glob("Assets:*:Checking"));
+        assert!(!checking.matches_glob("Liabilities:*:*:Checking"));
+
+        assert!(checking.matches_glob("Assets:**"));
+        assert!(Account::from("Assets:Cash").matches_glob("Assets:**"));
+        assert!(Account::from("Assets").matches_glob("Assets:**"));
+        assert!(!Account::from("Expenses:Food").matches_glob("Assets:**"));
+        assert!(checking.matches_glob("**:Checking"));
+        assert!(checking.matches_glob("Assets:**:Checking"));
+        assert!(!checking.matches_glob("Assets:**:Savings"));
+    }
+
+    #[test]
+    fn test_display_with_root_names() {
+        let mut names = RootNames::default();
+        assert_eq!(Account::from("Assets:US").display_with(&names), "Assets:US");
+
+        names.set(AccountType::Assets, "Activa".to_string());
+        assert_eq!(Account::from("Assets:US").display_with(&names), "Activa:US");
+        assert_eq!(AccountType::Assets.display_with(&names), "Activa");
+        assert_eq!(
+            Account::from("Expenses:Food").display_with(&names),
+            "Expenses:Food"
+        );
+        assert_eq!(Account::from("Assets:US").to_string(), "Assets:US");
+    }
+
+    #[test]
+    fn test_join() {
+        let bofa = Account::from_parts_unchecked(
+            AccountType::Assets,
+            vec!["US".to_string(), "BofA".to_string()],
+        );
+        assert_eq!(bofa, Account::from("Assets:US:BofA"));
+        assert_eq!(
+            Account::join(&bofa, "Checking"),
+            Account::from("Assets:US:BofA:Checking")
+        );
+        assert_eq!(Account::join(&Account::from("Assets"), "US:BofA"), bofa);
+    }
 }
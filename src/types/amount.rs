@@ -1,12 +1,17 @@
-use rust_decimal::Decimal;
-use std::convert::TryFrom;
-use std::{cmp, fmt};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::str::FromStr;
+use core::{cmp, error, fmt, ops};
+use rust_decimal::{Decimal, RoundingStrategy};
 use typed_builder::TypedBuilder;
 
 use crate::currency::Currency;
 
 /// A number of units of a certain commodity.
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Amount {
     /// The value of the amount.
     pub num: Decimal,
@@ -15,13 +20,206 @@ pub struct Amount {
     pub currency: Currency,
 }
 
+impl Amount {
+    /// Number of digits after the decimal point in the amount, e.g. 2 for `100.00 USD`.
+    pub fn scale(&self) -> u32 {
+        self.num.scale()
+    }
+
+    /// Returns the amount with exactly `scale` digits after the decimal point, padding with
+    /// zeros or rounding half away from zero as needed.
+    pub fn with_scale(&self, scale: u32) -> Amount {
+        let mut num = self.num;
+        num.rescale(scale);
+        Amount {
+            num,
+            currency: self.currency.clone(),
+        }
+    }
+
+    /// Returns the amount with trailing zeros stripped and negative zero turned into zero, so
+    /// that `-0.00 USD` displays as `0 USD`.
+    pub fn normalized(&self) -> Amount {
+        Amount {
+            num: self.num.normalize(),
+            currency: self.currency.clone(),
+        }
+    }
+
+    /// Returns just the normalized number, e.g. `154.2` for `154.20 USD`, for columns where the
+    /// currency is shown separately.
+    pub fn num_string(&self) -> String {
+        self.num.normalize().to_string()
+    }
+
+    /// Renders the amount like `Display` does, adjusted by `format`.
+    pub fn to_display_string(&self, format: &NumberFormat) -> String {
+        let mut res = String::new();
+        let negative = self.num.is_sign_negative() && !self.num.is_zero();
+        if negative && format.parens_for_negative {
+            res.push('(');
+            res.push_str(&self.num.abs().to_string());
+            res.push(')');
+        } else {
+            if format.show_plus && !negative && !self.num.is_zero() {
+                res.push('+');
+            }
+            res.push_str(&self.num.to_string());
+        }
+        if !format.omit_currency {
+            res.push(' ');
+            res.push_str(&self.currency);
+        }
+        res
+    }
+
+    /// Divides the number of the amount by `divisor`, e.g. a total price by a number of units to
+    /// get the per-unit price. Fails on a zero divisor or if the result does not fit a decimal.
+    pub fn checked_div(&self, divisor: Decimal) -> Result<Amount, AmountError> {
+        if divisor.is_zero() {
+            return Err(AmountError::DivisionByZero);
+        }
+        Ok(Amount {
+            num: self.num.checked_div(divisor).ok_or(AmountError::Overflow)?,
+            currency: self.currency.clone(),
+        })
+    }
+
+    /// Divides like [Amount::checked_div], rounding the result to `scale` digits after the
+    /// decimal point with `strategy`, e.g. to split `100.00 USD` in three as `33.33 USD`.
+    pub fn div_round(
+        &self,
+        divisor: Decimal,
+        scale: u32,
+        strategy: RoundingStrategy,
+    ) -> Result<Amount, AmountError> {
+        let mut quotient = self.checked_div(divisor)?;
+        quotient.num = quotient.num.round_dp_with_strategy(scale, strategy);
+        Ok(quotient)
+    }
+
+    /// Parses an amount written either as `100.00 USD` or as `USD 100.00`, deciding which token
+    /// is the number by trying to parse each as a decimal.
+    pub fn from_str_flexible(s: &str) -> Result<Amount, AmountError> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let [first, second] = tokens[..] else {
+            return Err(AmountError::Malformed(s.to_string()));
+        };
+        match (Decimal::from_str(first), Decimal::from_str(second)) {
+            (Ok(num), Err(_)) => Ok(Amount {
+                num,
+                currency: second.to_string(),
+            }),
+            (Err(_), Ok(num)) => Ok(Amount {
+                num,
+                currency: first.to_string(),
+            }),
+            _ => Err(AmountError::Ambiguous(s.to_string())),
+        }
+    }
+}
+
+impl From<(Decimal, Currency)> for Amount {
+    fn from((num, currency): (Decimal, Currency)) -> Self {
+        Amount { num, currency }
+    }
+}
+
+impl From<(Decimal, &str)> for Amount {
+    fn from((num, currency): (Decimal, &str)) -> Self {
+        Amount {
+            num,
+            currency: currency.to_string(),
+        }
+    }
+}
 
-impl std::fmt::Display for Amount {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Divides the number of the amount, keeping its currency, as [Amount::checked_div] does. A
+/// zero divisor gives [AmountError::DivisionByZero] instead of panicking.
+impl ops::Div<Decimal> for Amount {
+    type Output = Result<Amount, AmountError>;
+
+    fn div(self, divisor: Decimal) -> Result<Amount, AmountError> {
+        self.checked_div(divisor)
+    }
+}
+
+/// Parses an amount in beancount's `100.00 USD` form.
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let [num, currency] = tokens[..] else {
+            return Err(AmountError::Malformed(s.to_string()));
+        };
+        let num = Decimal::from_str(num).map_err(|_| AmountError::Malformed(s.to_string()))?;
+        if Decimal::from_str(currency).is_ok() {
+            return Err(AmountError::Malformed(s.to_string()));
+        }
+        Ok(Amount {
+            num,
+            currency: currency.to_string(),
+        })
+    }
+}
+
+/// Errors raised when building or parsing amounts.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AmountError {
+    /// The string is not a number followed by a currency.
+    Malformed(String),
+    /// It cannot be decided which token is the number, e.g. in `USD EUR`.
+    Ambiguous(String),
+    /// An incomplete amount has no number.
+    MissingNumber,
+    /// An incomplete amount has no currency.
+    MissingCurrency,
+    /// The arithmetic expression of a number cannot be evaluated.
+    InvalidExpression(String),
+    /// An amount is divided by zero.
+    DivisionByZero,
+    /// The result of an operation on an amount does not fit a decimal.
+    Overflow,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AmountError::Malformed(s) => write!(f, "malformed amount: {}", s),
+            AmountError::Ambiguous(s) => write!(f, "ambiguous amount: {}", s),
+            AmountError::MissingNumber => write!(f, "amount has no number"),
+            AmountError::MissingCurrency => write!(f, "amount has no currency"),
+            AmountError::InvalidExpression(s) => write!(f, "invalid number expression: {}", s),
+            AmountError::DivisionByZero => write!(f, "amount divided by zero"),
+            AmountError::Overflow => write!(f, "amount overflow"),
+        }
+    }
+}
+
+impl error::Error for AmountError {}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} {}", self.num, self.currency)
     }
 }
 
+/// Options for rendering an amount with `Amount::to_display_string`. The default renders the
+/// same text as `Display`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NumberFormat {
+    /// Leave out the currency, e.g. `154.20` instead of `154.20 USD`.
+    pub omit_currency: bool,
+
+    /// Prefix positive amounts with `+`, e.g. `+100 USD`, as in a column of changes. Zero is
+    /// left unsigned.
+    pub show_plus: bool,
+
+    /// Write negative amounts in parentheses without a sign, e.g. `(100.00) USD`, as in
+    /// accounting reports.
+    pub parens_for_negative: bool,
+}
 
 impl cmp::PartialOrd for Amount {
     fn partial_cmp(&self, other: &Amount) -> Option<cmp::Ordering> {
@@ -43,15 +241,41 @@ pub struct IncompleteAmount {
     /// The (optional) commodity of the amount.
     #[builder(default)]
     pub currency: Option<Currency>,
+
+    /// The arithmetic expression of the number as written in the input, e.g. `40.00/3`, when it
+    /// has not been evaluated into `num` yet. See [IncompleteAmount::evaluated].
+    #[builder(default)]
+    pub raw: Option<String>,
 }
 
+impl IncompleteAmount {
+    /// Returns the amount with its `raw` expression evaluated into `num`, or unchanged if it has
+    /// no expression. Fails with [AmountError::InvalidExpression] if the expression cannot be
+    /// evaluated.
+    pub fn evaluated(&self) -> Result<IncompleteAmount, AmountError> {
+        match self.raw.as_deref() {
+            Some(raw) => Ok(IncompleteAmount {
+                num: Some(evaluate_expression(raw)?),
+                currency: self.currency.clone(),
+                raw: None,
+            }),
+            None => Ok(self.clone()),
+        }
+    }
+}
+
+/// Renders the number, or the unevaluated expression if there is no number yet, followed by the
+/// currency if there is one.
 impl fmt::Display for IncompleteAmount {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut res = format!("{}", self.num.unwrap_or(Decimal::default()));
+        match (&self.num, &self.raw) {
+            (None, Some(raw)) => write!(f, "{}", raw)?,
+            (num, _) => write!(f, "{}", num.unwrap_or_default())?,
+        }
         if let Some(currency) = &self.currency {
-            res.push_str(&format!(" {}", currency));
+            write!(f, " {}", currency)?;
         }
-        write!(f, "{}", res)
+        Ok(())
     }
 }
 
@@ -66,15 +290,17 @@ impl cmp::PartialOrd for IncompleteAmount {
 }
 
 impl TryFrom<IncompleteAmount> for Amount {
-    type Error = ();
+    type Error = AmountError;
 
     fn try_from(val: IncompleteAmount) -> Result<Self, Self::Error> {
         match val {
+            IncompleteAmount { num: None, .. } => Err(AmountError::MissingNumber),
+            IncompleteAmount { currency: None, .. } => Err(AmountError::MissingCurrency),
             IncompleteAmount {
                 num: Some(num),
                 currency: Some(currency),
+                ..
             } => Ok(Amount { num, currency }),
-            _ => Err(()),
         }
     }
 }
@@ -84,6 +310,353 @@ impl From<Amount> for IncompleteAmount {
         IncompleteAmount {
             num: Some(val.num),
             currency: Some(val.currency),
+            raw: None,
+        }
+    }
+}
+
+/// Evaluates an arithmetic expression on numbers as beancount allows in amounts, e.g.
+/// `((40.00/3) + 5)`, with `+`, `-`, `*`, `/`, unary signs and parentheses.
+pub fn evaluate_expression(expr: &str) -> Result<Decimal, AmountError> {
+    let err = || AmountError::InvalidExpression(expr.to_string());
+    let mut parser = ExpressionParser {
+        chars: expr.chars().collect(),
+        pos: 0,
+    };
+    let num = parser.sum().ok_or_else(err)?;
+    if parser.peek().is_some() {
+        return Err(err());
+    }
+    Ok(num)
+}
+
+/// Recursive descent parser behind [evaluate_expression]. Each method consumes one precedence
+/// level and returns `None` on a syntax error, a division by zero or an overflow.
+struct ExpressionParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExpressionParser {
+    /// Skips whitespace and returns the next character without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn sum(&mut self) -> Option<Decimal> {
+        let mut num = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            num = if op == '+' {
+                num.checked_add(rhs)?
+            } else {
+                num.checked_sub(rhs)?
+            };
+        }
+        Some(num)
+    }
+
+    fn product(&mut self) -> Option<Decimal> {
+        let mut num = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            num = if op == '*' {
+                num.checked_mul(rhs)?
+            } else {
+                num.checked_div(rhs)?
+            };
+        }
+        Some(num)
+    }
+
+    fn factor(&mut self) -> Option<Decimal> {
+        match self.peek()? {
+            '-' => {
+                self.pos += 1;
+                self.factor().map(|num| -num)
+            }
+            '+' => {
+                self.pos += 1;
+                self.factor()
+            }
+            '(' => {
+                self.pos += 1;
+                let num = self.sum()?;
+                if self.peek()? != ')' {
+                    return None;
+                }
+                self.pos += 1;
+                Some(num)
+            }
+            _ => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos]
+                    .iter()
+                    .filter(|c| **c != ',')
+                    .collect();
+                Decimal::from_str(&number).ok()
+            }
+        }
+    }
+}
+
+/// Sums amounts per currency, e.g. to total the postings of a transaction.
+pub fn sum_amounts(amounts: impl IntoIterator<Item = Amount>) -> BTreeMap<Currency, Amount> {
+    let mut sums: BTreeMap<Currency, Amount> = BTreeMap::new();
+    for amount in amounts {
+        match sums.get_mut(&amount.currency) {
+            Some(sum) => sum.num += amount.num,
+            None => {
+                sums.insert(amount.currency.clone(), amount);
+            }
         }
     }
+    sums
+}
+
+/// Serializes an `Amount` as a single string in its `Display` form, e.g. `"154.20 USD"`, and
+/// parses it back with `FromStr`. Use it with `#[serde(with = "amount_as_string")]` on a field.
+#[cfg(feature = "serde")]
+pub mod amount_as_string {
+    use alloc::string::String;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use super::Amount;
+
+    pub fn serialize<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(amount)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd(num: &str) -> Amount {
+        Amount::builder()
+            .num(Decimal::from_str(num).unwrap())
+            .currency(Currency::from("USD"))
+            .build()
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("100.00 USD".parse::<Amount>(), Ok(usd("100.00")));
+        assert_eq!("-3492.02  USD".parse::<Amount>(), Ok(usd("-3492.02")));
+        assert_eq!(
+            "USD 100.00".parse::<Amount>(),
+            Err(AmountError::Malformed("USD 100.00".to_string()))
+        );
+        assert!("100.00".parse::<Amount>().is_err());
+    }
+
+    #[test]
+    fn test_from_str_flexible() {
+        assert_eq!(Amount::from_str_flexible("100.00 USD"), Ok(usd("100.00")));
+        assert_eq!(Amount::from_str_flexible("USD 100.00"), Ok(usd("100.00")));
+        assert_eq!(
+            Amount::from_str_flexible("USD EUR"),
+            Err(AmountError::Ambiguous("USD EUR".to_string()))
+        );
+        assert_eq!(
+            Amount::from_str_flexible("100 USD EUR"),
+            Err(AmountError::Malformed("100 USD EUR".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_try_from_incomplete() {
+        assert_eq!(
+            Amount::try_from(IncompleteAmount::from(usd("10"))),
+            Ok(usd("10"))
+        );
+        assert_eq!(
+            Amount::try_from(
+                IncompleteAmount::builder()
+                    .currency(Some("USD".to_string()))
+                    .build()
+            ),
+            Err(AmountError::MissingNumber)
+        );
+        assert_eq!(
+            Amount::try_from(IncompleteAmount::builder().num(Some(Decimal::ONE)).build()),
+            Err(AmountError::MissingCurrency)
+        );
+    }
+
+    #[test]
+    fn test_evaluated() {
+        let amount = IncompleteAmount::builder()
+            .currency(Some(Currency::from("USD")))
+            .raw(Some("40.00/3".to_string()))
+            .build();
+        assert_eq!(amount.to_string(), "40.00/3 USD");
+
+        let evaluated = amount.evaluated().unwrap();
+        let num = evaluated.num.unwrap();
+        assert_eq!(num, Decimal::from_str("40.00").unwrap() / Decimal::from(3));
+        assert!(num.to_string().starts_with("13.333333"));
+        assert_eq!(evaluated.raw, None);
+
+        let invalid = IncompleteAmount::builder()
+            .raw(Some("40.00/".to_string()))
+            .build();
+        assert_eq!(
+            invalid.evaluated(),
+            Err(AmountError::InvalidExpression("40.00/".to_string()))
+        );
+
+        let plain = IncompleteAmount::from(Amount::from((Decimal::from(40), "USD")));
+        assert_eq!(plain.evaluated(), Ok(plain.clone()));
+    }
+
+    #[test]
+    fn test_evaluate_expression() {
+        let eval = |expr: &str| evaluate_expression(expr).map(|num| num.to_string());
+        assert_eq!(eval("((40.00/4) + 5) * 3"), Ok("45.00".to_string()));
+        assert_eq!(eval("-45.00 + 2 * 3"), Ok("-39.00".to_string()));
+        assert_eq!(eval("1,000.50"), Ok("1000.50".to_string()));
+        assert_eq!(
+            eval("(1 + 2"),
+            Err(AmountError::InvalidExpression("(1 + 2".to_string()))
+        );
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("1 2").is_err());
+    }
+
+    #[test]
+    fn test_from_tuple() {
+        assert_eq!(Amount::from((Decimal::from(100), "USD")), usd("100"));
+        assert_eq!(
+            Amount::from((Decimal::from_str("154.20").unwrap(), Currency::from("USD"))),
+            usd("154.20")
+        );
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(usd("100.00").scale(), 2);
+        assert_eq!(usd("100").scale(), 0);
+
+        let rescaled = usd("100").with_scale(2);
+        assert_eq!(rescaled.scale(), 2);
+        assert_eq!(rescaled.to_string(), "100.00 USD");
+        assert_eq!(usd("13.335").with_scale(2).to_string(), "13.34 USD");
+    }
+
+    #[test]
+    fn test_normalized() {
+        let zero = usd("-0.00").normalized();
+        assert_eq!(zero.num, Decimal::ZERO);
+        assert!(zero.num.is_sign_positive());
+        assert_eq!(zero.to_string(), "0 USD");
+        assert_eq!(usd("154.20").normalized().to_string(), "154.2 USD");
+    }
+
+    #[test]
+    fn test_num_string() {
+        assert_eq!(usd("154.20").num_string(), "154.2");
+        assert_eq!(usd("-0.00").num_string(), "0");
+
+        let number_only = NumberFormat {
+            omit_currency: true,
+            ..NumberFormat::default()
+        };
+        assert_eq!(usd("154.20").to_display_string(&number_only), "154.20");
+        assert_eq!(
+            usd("154.20").to_display_string(&NumberFormat::default()),
+            "154.20 USD"
+        );
+    }
+
+    #[test]
+    fn test_show_plus() {
+        let format = NumberFormat {
+            show_plus: true,
+            ..NumberFormat::default()
+        };
+        assert_eq!(usd("100").to_display_string(&format), "+100 USD");
+        assert_eq!(usd("-37.45").to_display_string(&format), "-37.45 USD");
+        assert_eq!(usd("0.00").to_display_string(&format), "0.00 USD");
+    }
+
+    #[test]
+    fn test_parens_for_negative() {
+        let format = NumberFormat {
+            parens_for_negative: true,
+            ..NumberFormat::default()
+        };
+        assert_eq!(usd("-100.00").to_display_string(&format), "(100.00) USD");
+        assert_eq!(usd("100.00").to_display_string(&format), "100.00 USD");
+        assert_eq!(usd("-0.00").to_display_string(&format), "0.00 USD");
+    }
+
+    #[test]
+    fn test_div() {
+        assert_eq!(usd("1830.70") / Decimal::from(10), Ok(usd("183.07")));
+        assert_eq!(
+            usd("1830.70").checked_div(Decimal::from(10)),
+            Ok(usd("183.07"))
+        );
+        assert_eq!(
+            usd("100.00").div_round(Decimal::from(3), 2, RoundingStrategy::MidpointAwayFromZero),
+            Ok(usd("33.33"))
+        );
+        assert_eq!(
+            usd("1830.70") / Decimal::ZERO,
+            Err(AmountError::DivisionByZero)
+        );
+        assert_eq!(
+            usd("1830.70").checked_div(Decimal::ZERO),
+            Err(AmountError::DivisionByZero)
+        );
+        assert_eq!(
+            usd("100.00").div_round(Decimal::ZERO, 2, RoundingStrategy::ToZero),
+            Err(AmountError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_sum_amounts() {
+        let eur = |num: &str| Amount::from((Decimal::from_str(num).unwrap(), "EUR"));
+        let sums = sum_amounts(vec![usd("100.00"), eur("20"), usd("-30.50"), eur("2.5")]);
+        assert_eq!(sums.len(), 2);
+        assert_eq!(sums["USD"], usd("69.50"));
+        assert_eq!(sums["EUR"], eur("22.5"));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_amount_as_string() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Row {
+            #[serde(with = "amount_as_string")]
+            amount: Amount,
+        }
+
+        let row = Row {
+            amount: usd("154.20"),
+        };
+        let json = serde_json::to_string(&row).unwrap();
+        assert_eq!(json, r#"{"amount":"154.20 USD"}"#);
+        assert_eq!(serde_json::from_str::<Row>(&json).unwrap(), row);
+        assert!(serde_json::from_str::<Row>(r#"{"amount":"USD"}"#).is_err());
+    }
 }
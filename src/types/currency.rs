@@ -1,3 +1,6 @@
+use alloc::string::{String, ToString};
+use core::{error, fmt, str::FromStr};
+
 /// # Commodities / Currencies
 ///
 /// Accounts contain currencies, which we sometimes also call commodities (we use both terms
@@ -48,3 +51,145 @@
 /// currencies. It is entirely optional: currencies come into being as you use them. The purpose of
 /// the directive is simply to attach metadata to it.
 pub type Currency = String;
+
+/// Maximum length of a currency name.
+pub const MAX_CURRENCY_LEN: usize = 24;
+
+/// Checks `s` against the currency name rules described on [Currency], reporting the first rule
+/// that is broken.
+pub fn validate_currency(s: &str) -> Result<(), CurrencyError> {
+    if s.chars().count() > MAX_CURRENCY_LEN {
+        return Err(CurrencyError::TooLong);
+    }
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_uppercase() => {}
+        _ => return Err(CurrencyError::BadStart),
+    }
+    if let Some(c) = chars.next_back() {
+        if !(c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            return Err(CurrencyError::BadEnd);
+        }
+    }
+    let allowed = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || "'._-".contains(c);
+    match chars.find(|&c| !allowed(c)) {
+        Some(c) => Err(CurrencyError::BadChar(c)),
+        None => Ok(()),
+    }
+}
+
+/// The rule broken by an invalid currency name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CurrencyError {
+    /// The name is longer than [MAX_CURRENCY_LEN] characters.
+    TooLong,
+    /// The name is empty or does not start with a capital letter.
+    BadStart,
+    /// The name does not end with a capital letter or a digit.
+    BadEnd,
+    /// The name contains a character other than capital letters, digits and `'._-`.
+    BadChar(char),
+}
+
+impl fmt::Display for CurrencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CurrencyError::TooLong => {
+                write!(f, "currency is longer than {} characters", MAX_CURRENCY_LEN)
+            }
+            CurrencyError::BadStart => write!(f, "currency must start with a capital letter"),
+            CurrencyError::BadEnd => {
+                write!(f, "currency must end with a capital letter or a digit")
+            }
+            CurrencyError::BadChar(c) => write!(f, "invalid character in currency: {:?}", c),
+        }
+    }
+}
+
+impl error::Error for CurrencyError {}
+
+/// A pair of currencies used to quote prices: one unit of `base` is worth some amount of
+/// `quote`. Written `BASE/QUOTE`, e.g. `USD/CAD`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct CurrencyPair {
+    /// The currency being priced.
+    pub base: Currency,
+
+    /// The currency the price is expressed in.
+    pub quote: Currency,
+}
+
+impl CurrencyPair {
+    pub fn new(base: Currency, quote: Currency) -> CurrencyPair {
+        CurrencyPair { base, quote }
+    }
+}
+
+impl fmt::Display for CurrencyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
+    }
+}
+
+/// Error returned when a string cannot be parsed as a `CurrencyPair`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CurrencyPairError(pub String);
+
+impl fmt::Display for CurrencyPairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid currency pair: {}", self.0)
+    }
+}
+
+impl error::Error for CurrencyPairError {}
+
+impl FromStr for CurrencyPair {
+    type Err = CurrencyPairError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((base, quote))
+                if !base.is_empty() && !quote.is_empty() && !quote.contains('/') =>
+            {
+                Ok(CurrencyPair::new(base.to_string(), quote.to_string()))
+            }
+            _ => Err(CurrencyPairError(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_pair() {
+        let pair: CurrencyPair = "USD/CAD".parse().unwrap();
+        assert_eq!(
+            pair,
+            CurrencyPair::new(Currency::from("USD"), Currency::from("CAD"))
+        );
+        assert_eq!(pair.to_string(), "USD/CAD");
+
+        assert!("USD".parse::<CurrencyPair>().is_err());
+        assert!("USD/".parse::<CurrencyPair>().is_err());
+        assert!("USD/CAD/EUR".parse::<CurrencyPair>().is_err());
+    }
+
+    #[test]
+    fn test_validate_currency() {
+        for valid in ["USD", "MSFT", "AIRMILE", "V", "BRK.B", "IRA_USD", "A'1"] {
+            assert_eq!(validate_currency(valid), Ok(()), "{}", valid);
+        }
+        assert_eq!(
+            validate_currency("ABCDEFGHIJKLMNOPQRSTUVWXY"),
+            Err(CurrencyError::TooLong)
+        );
+        assert_eq!(validate_currency(""), Err(CurrencyError::BadStart));
+        assert_eq!(validate_currency("1USD"), Err(CurrencyError::BadStart));
+        assert_eq!(validate_currency("usd"), Err(CurrencyError::BadStart));
+        assert_eq!(validate_currency("USD-"), Err(CurrencyError::BadEnd));
+        assert_eq!(validate_currency("US$D"), Err(CurrencyError::BadChar('$')));
+        assert_eq!(validate_currency("US D"), Err(CurrencyError::BadChar(' ')));
+    }
+}
@@ -1,6 +1,11 @@
-use std::{fmt, fmt::Display};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{error, fmt, fmt::Display, str::FromStr};
 
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, TimeZone};
 
 /// Represents a beancount date. It can be created using the `from_*_unchecked` methods.
 /// Alternatively, with the `chrono` feature enabled, it can be converted from a `NaiveDate`.
@@ -19,6 +24,7 @@ use chrono::NaiveDate;
 /// let today: Date = chrono::Local::today().naive_local().into();
 /// ```ignore
 #[derive(Eq, PartialEq, Debug, Clone, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Date(String);
 
 impl Date {
@@ -27,12 +33,134 @@ impl Date {
     }
 
     pub fn from_string_unchecked(s: String) -> Date {
-        Date(s.into())
+        Date(s)
     }
 
     pub fn from_cow_unchecked(s: &str) -> Date {
         Date(s.to_string())
     }
+
+    /// Parses a date like the `FromStr` implementation, but also accepts a two-digit year such as
+    /// `14-02-03`. Two-digit years below `pivot` are placed in the 2000s, the others in the 1900s,
+    /// so with a pivot of 70, `14` becomes 2014 and `99` becomes 1999.
+    pub fn from_str_with_century(s: &str, pivot: i32) -> Result<Date, DateError> {
+        let (year, rest) = s
+            .split_once(['-', '/'])
+            .ok_or_else(|| DateError::Malformed(s.to_string()))?;
+        if year.len() != 2 {
+            return s.parse();
+        }
+        let year: i32 = year
+            .parse()
+            .map_err(|_| DateError::Malformed(s.to_string()))?;
+        let century = if year < pivot { 2000 } else { 1900 };
+        format!("{}-{}", century + year, rest)
+            .parse()
+            .map_err(|e| match e {
+                DateError::Malformed(_) => DateError::Malformed(s.to_string()),
+                DateError::Invalid(_) => DateError::Invalid(s.to_string()),
+            })
+    }
+
+    /// Converts the date to a chrono `NaiveDate`, or `None` if it is not a valid date.
+    pub fn to_naive_date(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(&self.0, "%Y-%m-%d").ok()
+    }
+}
+
+/// A half-open range of dates: `from` is included and `to` is not, so consecutive ranges such as
+/// two months share a bound without overlapping.
+#[derive(Eq, PartialEq, Debug, Clone, Hash)]
+pub struct DateRange {
+    pub from: Date,
+    pub to: Date,
+}
+
+impl DateRange {
+    pub fn new(from: Date, to: Date) -> DateRange {
+        DateRange { from, to }
+    }
+
+    /// The range covering a calendar month, or `None` if the month does not exist.
+    pub fn month(year: i32, month: u32) -> Option<DateRange> {
+        let from = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let to = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        Some(DateRange::new(from.into(), to.into()))
+    }
+
+    /// The range covering a calendar year, or `None` if it is out of chrono's range.
+    pub fn year(year: i32) -> Option<DateRange> {
+        let from = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        let to = NaiveDate::from_ymd_opt(year + 1, 1, 1)?;
+        Some(DateRange::new(from.into(), to.into()))
+    }
+
+    /// Returns true if `date` is on or after `from` and before `to`.
+    pub fn contains(&self, date: &Date) -> bool {
+        &self.from <= date && date < &self.to
+    }
+
+    /// Number of days in the range, `to` excluded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either bound is not a valid date, which can only happen for ranges built from
+    /// unchecked dates.
+    pub fn days(&self) -> i64 {
+        let from = self.from.to_naive_date().expect("invalid range start");
+        let to = self.to.to_naive_date().expect("invalid range end");
+        (to - from).num_days()
+    }
+}
+
+/// Error returned when a string cannot be parsed as a `Date`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DateError {
+    /// The string is not in the `YYYY-MM-DD` or `YYYY/MM/DD` format.
+    Malformed(String),
+    /// The string is well formed but does not name a day of the calendar, e.g. `2014-02-30`.
+    Invalid(String),
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateError::Malformed(s) => write!(f, "malformed date: {}", s),
+            DateError::Invalid(s) => write!(f, "invalid date: {}", s),
+        }
+    }
+}
+
+impl error::Error for DateError {}
+
+/// Parses a date written as `YYYY-MM-DD` or `YYYY/MM/DD`, checking that it exists in the
+/// calendar. The resulting date always uses dashes.
+impl FromStr for Date {
+    type Err = DateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(['-', '/']).collect();
+        let well_formed = parts.len() == 3
+            && parts[0].len() == 4
+            && parts[1].len() == 2
+            && parts[2].len() == 2
+            && parts.iter().all(|p| p.bytes().all(|b| b.is_ascii_digit()));
+        if !well_formed {
+            return Err(DateError::Malformed(s.to_string()));
+        }
+        let (year, month, day) = (
+            parts[0].parse().unwrap(),
+            parts[1].parse().unwrap(),
+            parts[2].parse().unwrap(),
+        );
+        NaiveDate::from_ymd_opt(year, month, day)
+            .map(Date::from)
+            .ok_or_else(|| DateError::Invalid(s.to_string()))
+    }
 }
 
 impl From<Date> for String {
@@ -53,10 +181,85 @@ impl From<NaiveDate> for Date {
     }
 }
 
+/// Takes the date portion of the datetime, in its own timezone. This covers `DateTime<Utc>` as
+/// well as local and fixed-offset datetimes.
+impl<Tz: TimeZone> From<DateTime<Tz>> for Date {
+    fn from(d: DateTime<Tz>) -> Self {
+        d.date_naive().into()
+    }
+}
+
 #[test]
 fn test_date_from_chrono() {
     assert_eq!(
-        Date::from(chrono::NaiveDate::from_ymd(2020, 05, 05)),
+        Date::from(chrono::NaiveDate::from_ymd_opt(2020, 5, 5).unwrap()),
         Date::from_str_unchecked("2020-05-05")
     );
 }
+
+#[test]
+fn test_date_from_str() {
+    assert_eq!(
+        "2014-02-03".parse::<Date>(),
+        Ok(Date::from_str_unchecked("2014-02-03"))
+    );
+    assert_eq!(
+        "2014/02/03".parse::<Date>(),
+        Ok(Date::from_str_unchecked("2014-02-03"))
+    );
+    assert_eq!(
+        "2014-2-3".parse::<Date>(),
+        Err(DateError::Malformed("2014-2-3".to_string()))
+    );
+    assert_eq!(
+        "2014-02-30".parse::<Date>(),
+        Err(DateError::Invalid("2014-02-30".to_string()))
+    );
+}
+
+#[test]
+fn test_date_from_str_with_century() {
+    assert_eq!(
+        Date::from_str_with_century("14-02-03", 70),
+        Ok(Date::from_str_unchecked("2014-02-03"))
+    );
+    assert_eq!(
+        Date::from_str_with_century("99-01-01", 70),
+        Ok(Date::from_str_unchecked("1999-01-01"))
+    );
+    assert_eq!(
+        Date::from_str_with_century("2014-02-03", 70),
+        Ok(Date::from_str_unchecked("2014-02-03"))
+    );
+    assert_eq!(
+        Date::from_str_with_century("14-02-30", 70),
+        Err(DateError::Invalid("14-02-30".to_string()))
+    );
+}
+
+#[test]
+fn test_date_from_chrono_datetime() {
+    use chrono::{FixedOffset, Utc};
+
+    let utc = Utc.with_ymd_and_hms(2020, 5, 5, 23, 30, 0).unwrap();
+    assert_eq!(Date::from(utc), Date::from_str_unchecked("2020-05-05"));
+
+    let tokyo = utc.with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap());
+    assert_eq!(Date::from(tokyo), Date::from_str_unchecked("2020-05-06"));
+}
+
+#[test]
+fn test_date_range() {
+    let february = DateRange::month(2024, 2).unwrap();
+    assert!(february.contains(&Date::from_str_unchecked("2024-02-01")));
+    assert!(february.contains(&Date::from_str_unchecked("2024-02-29")));
+    assert!(!february.contains(&Date::from_str_unchecked("2024-03-01")));
+    assert!(!february.contains(&Date::from_str_unchecked("2024-01-31")));
+    assert_eq!(february.days(), 29);
+
+    let december = DateRange::month(2014, 12).unwrap();
+    assert_eq!(december.to, Date::from_str_unchecked("2015-01-01"));
+    assert_eq!(DateRange::month(2014, 13), None);
+
+    assert_eq!(DateRange::year(2014).unwrap().days(), 365);
+}
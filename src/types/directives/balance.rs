@@ -1,7 +1,18 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::{error, fmt, str::FromStr};
+
 use rust_decimal::Decimal;
 use typed_builder::TypedBuilder;
 
-use crate::{account::Account, amount::Amount, types::date::Date};
+use crate::{
+    account::Account,
+    amount::{Amount, AmountError},
+    currency::{validate_currency, CurrencyError},
+    types::date::Date,
+};
 /// # Balance Assertion Directive
 ///
 /// Balance assertions are used to verify account balances at specific points in time.
@@ -29,7 +40,7 @@ use crate::{account::Account, amount::Amount, types::date::Date};
 /// - Beancount reports an error if the actual balance doesn't match the assertion.
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.l0pvgeniwvq8>
 #[derive(Clone, Debug, PartialEq, TypedBuilder)]
-pub struct Balance {
+pub struct Balance<'a> {
     /// Date of the balance.
     pub date: Date,
 
@@ -41,18 +52,119 @@ pub struct Balance {
 
     #[builder(default)]
     pub tolerance: Option<Decimal>,
+
+    /// Ties the directive to the lifetime of [Directive](super::Directive). It holds no borrowed
+    /// data, as all its fields are owned.
+    #[builder(default, setter(skip))]
+    phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> Balance<'a> {
+    /// Asserts that `account` holds exactly `amount` at the beginning of `date`, which may be
+    /// anything convertible into a [Date]. No check is made; see [Balance::try_new].
+    pub fn on(date: impl Into<Date>, account: Account, amount: Amount) -> Balance<'a> {
+        Balance::builder()
+            .date(date.into())
+            .account(account)
+            .amount(amount)
+            .build()
+    }
+
+    /// Builds a balance assertion, checking that the currency of `amount` is a valid currency
+    /// name and that `tolerance`, if any, is not negative.
+    pub fn try_new(
+        date: Date,
+        account: Account,
+        amount: Amount,
+        tolerance: Option<Decimal>,
+    ) -> Result<Balance<'a>, BalanceError> {
+        validate_currency(&amount.currency).map_err(BalanceError::Currency)?;
+        if let Some(tolerance) = tolerance.filter(|t| t.is_sign_negative() && !t.is_zero()) {
+            return Err(BalanceError::NegativeTolerance(tolerance));
+        }
+        Ok(Balance {
+            date,
+            account,
+            amount,
+            tolerance,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Parses the amount of a balance assertion with its optional tolerance. The tolerance may
+    /// come before the currency, as beancount writes it in `100.00 ~ 0.05 USD`, or after the
+    /// amount, as in `100.00 USD ~ 0.05`.
+    pub fn parse_amount_tolerance(s: &str) -> Result<(Amount, Option<Decimal>), BalanceError> {
+        let Some((amount, tolerance)) = s.split_once('~') else {
+            return Ok((s.parse().map_err(BalanceError::Amount)?, None));
+        };
+        let tokens: Vec<&str> = tolerance.split_whitespace().collect();
+        let (tolerance, amount) = match tokens[..] {
+            [tolerance] => (tolerance, amount.parse()),
+            [tolerance, currency] => (tolerance, format!("{} {}", amount, currency).parse()),
+            _ => return Err(BalanceError::Tolerance(s.to_string())),
+        };
+        let tolerance =
+            Decimal::from_str(tolerance).map_err(|_| BalanceError::Tolerance(s.to_string()))?;
+        Ok((amount.map_err(BalanceError::Amount)?, Some(tolerance)))
+    }
+}
+
+/// Errors raised when building an invalid balance assertion, or a transaction that does not
+/// balance.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BalanceError {
+    /// The currency of the amount is not a valid currency name.
+    Currency(CurrencyError),
+    /// The tolerance is below zero.
+    NegativeTolerance(Decimal),
+    /// The amount cannot be parsed.
+    Amount(AmountError),
+    /// The tolerance clause is not a single number after `~`.
+    Tolerance(String),
+    /// More than one posting of a transaction has no amount, so the residual cannot be assigned.
+    MultipleElidedPostings,
+    /// The postings of a transaction do not sum to zero; holds the residual.
+    Unbalanced(Amount),
+}
+
+impl fmt::Display for BalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BalanceError::Currency(e) => write!(f, "invalid balance currency: {}", e),
+            BalanceError::NegativeTolerance(t) => write!(f, "negative balance tolerance: {}", t),
+            BalanceError::Amount(e) => write!(f, "invalid balance amount: {}", e),
+            BalanceError::Tolerance(s) => write!(f, "invalid balance tolerance: {}", s),
+            BalanceError::MultipleElidedPostings => {
+                write!(f, "more than one posting has no amount")
+            }
+            BalanceError::Unbalanced(residual) => {
+                write!(f, "transaction does not balance: residual of {}", residual)
+            }
+        }
+    }
 }
 
+impl error::Error for BalanceError {}
 
-impl std::fmt::Display for Balance {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{} balance {} {}", self.date, self.account, self.amount)
+/// Renders the assertion; a tolerance is written between the number and the currency, as in
+/// `-3492.02 ~ 0.01 USD`.
+impl core::fmt::Display for Balance<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.tolerance {
+            Some(tolerance) => write!(
+                f,
+                "{} balance {} {} ~ {} {}",
+                self.date, self.account, self.amount.num, tolerance, self.amount.currency
+            ),
+            None => write!(f, "{} balance {} {}", self.date, self.account, self.amount),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use core::str::FromStr;
 
     use crate::currency::Currency;
 
@@ -77,5 +189,75 @@ mod tests {
             .build();
         
         assert_eq!(balance.to_string(), "2014-12-26 balance Liabilities:US:CreditCard -3492.02 USD");
+
+        let balance = Balance {
+            tolerance: Some(Decimal::from_str("0.01").unwrap()),
+            ..balance
+        };
+        assert_eq!(
+            balance.to_string(),
+            "2014-12-26 balance Liabilities:US:CreditCard -3492.02 ~ 0.01 USD"
+        );
+    }
+
+    #[test]
+    fn test_try_new() {
+        let account = Account::from("Liabilities:US:CreditCard");
+        let date = Date::from_str_unchecked("2014-12-26");
+        let amount =
+            |currency: &str| Amount::from((Decimal::from_str("-3492.02").unwrap(), currency));
+
+        let balance = Balance::try_new(
+            date.clone(),
+            account.clone(),
+            amount("USD"),
+            Some(Decimal::from_str("0.01").unwrap()),
+        )
+        .unwrap();
+        assert_eq!(balance.tolerance, Some(Decimal::from_str("0.01").unwrap()));
+
+        assert_eq!(
+            Balance::try_new(
+                date.clone(),
+                account.clone(),
+                amount("USD"),
+                Some(Decimal::from_str("-0.01").unwrap()),
+            ),
+            Err(BalanceError::NegativeTolerance(
+                Decimal::from_str("-0.01").unwrap()
+            ))
+        );
+        assert_eq!(
+            Balance::try_new(date, account, amount("usd"), None),
+            Err(BalanceError::Currency(CurrencyError::BadStart))
+        );
+    }
+
+    #[test]
+    fn test_parse_amount_tolerance() {
+        let usd = Amount::from((Decimal::from_str("100.00").unwrap(), "USD"));
+        let tolerance = Some(Decimal::from_str("0.05").unwrap());
+        assert_eq!(
+            Balance::parse_amount_tolerance("100.00 USD"),
+            Ok((usd.clone(), None))
+        );
+        assert_eq!(
+            Balance::parse_amount_tolerance("100.00 USD ~ 0.05"),
+            Ok((usd.clone(), tolerance))
+        );
+        assert_eq!(
+            Balance::parse_amount_tolerance("100.00 ~ 0.05 USD"),
+            Ok((usd, tolerance))
+        );
+        assert_eq!(
+            Balance::parse_amount_tolerance("100.00 USD ~ abc"),
+            Err(BalanceError::Tolerance("100.00 USD ~ abc".to_string()))
+        );
+        assert_eq!(
+            Balance::parse_amount_tolerance("USD"),
+            Err(BalanceError::Amount(AmountError::Malformed(
+                "USD".to_string()
+            )))
+        );
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,6 @@
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+
 use typed_builder::TypedBuilder;
 
 use crate::account::AccountType;
@@ -40,19 +43,21 @@ use crate::account::AccountType::*;
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.e2iyrfrmstl>
 
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct BcOption {
+pub struct BcOption<'a> {
     /// Name of the option.
-    pub name: String,
+    #[builder(setter(into))]
+    pub name: Cow<'a, str>,
 
     /// Value of the option.
-    pub val: String,
+    #[builder(setter(into))]
+    pub val: Cow<'a, str>,
 
     /// Source string from the parsed input
     #[builder(default)]
-    pub source: Option<String>,
+    pub source: Option<Cow<'a, str>>,
 }
 
-impl BcOption {
+impl BcOption<'_> {
     /// Determines if the current option specifies a root account name change.
     /// For example, the following line will rename the 'Assets' root account to 'Activa':
     /// ```ignorebeancount
@@ -74,8 +79,13 @@ impl BcOption {
 }
 
 
-impl std::fmt::Display for BcOption {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Renders the source text of the option when it is known, so that parsed input is written back
+/// unchanged, and the name and value otherwise.
+impl core::fmt::Display for BcOption<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if let Some(source) = &self.source {
+            return f.write_str(source);
+        }
         write!(f, "option {} {}", self.name, self.val)
     }
 }
@@ -89,4 +99,15 @@ mod tests {
         let option = BcOption::builder().name("title".to_string()).val("Ed's Personal Ledger".to_string()).build();
         assert_eq!(option.to_string(), "option title Ed's Personal Ledger");
     }
+
+    #[test]
+    fn test_display_source() {
+        let source = r#"option  "title"   "Ed's Personal Ledger""#;
+        let option = BcOption::builder()
+            .name("title")
+            .val("Ed's Personal Ledger")
+            .source(Some(source.into()))
+            .build();
+        assert_eq!(option.to_string(), source);
+    }
 }
\ No newline at end of file
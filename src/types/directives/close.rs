@@ -1,4 +1,5 @@
-use std::fmt;
+use core::fmt;
+use core::marker::PhantomData;
 
 use typed_builder::TypedBuilder;
 
@@ -31,16 +32,27 @@ use crate::types::date::Date;
 /// It's recommended to close accounts in your ledger when they close in reality to keep your records tidy.
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.wf248e8stnac>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Close {
+pub struct Close<'a> {
     /// Date the account was closed.
     pub date: Date,
 
     /// Account being closed.
     pub account: Account,
+
+    /// Ties the directive to the lifetime of [Directive](super::Directive). It holds no borrowed
+    /// data, as all its fields are owned.
+    #[builder(default, setter(skip))]
+    phantom: PhantomData<&'a ()>,
 }
 
+impl<'a> Close<'a> {
+    /// Closes `account` on `date`, which may be anything convertible into a [Date].
+    pub fn on(date: impl Into<Date>, account: Account) -> Close<'a> {
+        Close::builder().date(date.into()).account(account).build()
+    }
+}
 
-impl fmt::Display for Close {
+impl fmt::Display for Close<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} close {}", self.date, self.account)
     }
@@ -52,10 +64,10 @@ mod tests {
 
     #[test]
     fn test_display() {
-        let close = Close {
-            date: Date::from_str_unchecked("2016-11-28"),
-            account: Account::from("Liabilities:CreditCard:CapitalOne"),
-        };
+        let close = Close::builder()
+            .date(Date::from_str_unchecked("2016-11-28"))
+            .account(Account::from("Liabilities:CreditCard:CapitalOne"))
+            .build();
         assert_eq!(close.to_string(), "2016-11-28 close Liabilities:CreditCard:CapitalOne");
     }
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
+use core::marker::PhantomData;
+
 use typed_builder::TypedBuilder;
 
 use crate::currency::Currency;
+use crate::metadata::{write_meta, Meta};
 use crate::types::date::Date;
 /// # Commodity Directive
 ///
@@ -35,10 +38,64 @@ use crate::types::date::Date;
 /// ```ignore
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.a3si01ejc035>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Commodity {
+pub struct Commodity<'a> {
     /// Date the commodity was declared.
     pub date: Date,
 
     /// Commodity name.
     pub name: Currency,
+
+    /// Metadata attached to the commodity, e.g. its full name or asset class.
+    #[builder(default)]
+    pub meta: Meta,
+
+    /// Ties the directive to the lifetime of [Directive](super::Directive). It holds no borrowed
+    /// data, as all its fields are owned.
+    #[builder(default, setter(skip))]
+    phantom: PhantomData<&'a ()>,
+}
+
+impl core::fmt::Display for Commodity<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{} commodity {}", self.date, self.name)?;
+        write_meta(f, &self.meta, "  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::MetaValue;
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let commodity = Commodity::builder()
+            .date(Date::from_str_unchecked("1867-07-01"))
+            .name(Currency::from("CAD"))
+            .build();
+        assert_eq!(commodity.to_string(), "1867-07-01 commodity CAD");
+    }
+
+    #[test]
+    fn test_display_with_meta() {
+        let mut meta = Meta::new();
+        meta.insert(
+            "name".to_string(),
+            MetaValue::Text("Hooli Corporation Class C Shares".to_string()),
+        );
+        meta.insert(
+            "asset-class".to_string(),
+            MetaValue::Text("stock".to_string()),
+        );
+        let commodity = Commodity::builder()
+            .date(Date::from_str_unchecked("2012-01-01"))
+            .name(Currency::from("HOOL"))
+            .meta(meta)
+            .build();
+        assert_eq!(
+            commodity.to_string(),
+            "2012-01-01 commodity HOOL\n  asset-class: \"stock\"\n  name: \"Hooli Corporation Class C Shares\""
+        );
+    }
 }
@@ -1,6 +1,12 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+
 use typed_builder::TypedBuilder;
 
 use crate::types::date::Date;
+use crate::util::escape_string;
+
+use super::DirectiveError;
 
 /// # Custom Directive
 ///
@@ -56,13 +62,69 @@ use crate::types::date::Date;
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.20klpeqb6ajy>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Custom {
+pub struct Custom<'a> {
     /// Date associated with the custom directive.
     pub date: Date,
 
     /// Custom directive name.
-    pub name: String,
+    #[builder(setter(into))]
+    pub name: Cow<'a, str>,
 
     /// Arbitrary number of custom directive arguments.
-    pub args: Vec<String>,
+    pub args: Vec<Cow<'a, str>>,
+}
+
+impl Custom<'_> {
+    /// Checks that the custom directive has a name, which beancount does not allow to be empty
+    /// or blank. The arguments are free-form and not checked.
+    pub fn validate(&self) -> Result<(), DirectiveError> {
+        if self.name.trim().is_empty() {
+            return Err(DirectiveError::Blank("custom directive name"));
+        }
+        Ok(())
+    }
+}
+
+impl core::fmt::Display for Custom<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{} custom \"{}\"", self.date, escape_string(&self.name))?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let custom = Custom::builder()
+            .date(Date::from_str_unchecked("2014-07-09"))
+            .name("budget".to_string())
+            .args(vec![r#""...""#.into(), "TRUE".into(), "45.30 USD".into()])
+            .build();
+        assert_eq!(
+            custom.to_string(),
+            r#"2014-07-09 custom "budget" "..." TRUE 45.30 USD"#
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let custom = |name: &str| {
+            Custom::builder()
+                .date(Date::from_str_unchecked("2014-07-09"))
+                .name(name.to_string())
+                .args(vec![])
+                .build()
+        };
+        assert_eq!(custom("budget").validate(), Ok(()));
+        assert_eq!(
+            custom("").validate(),
+            Err(DirectiveError::Blank("custom directive name"))
+        );
+    }
 }
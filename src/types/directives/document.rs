@@ -1,7 +1,10 @@
+use alloc::borrow::Cow;
+
 use typed_builder::TypedBuilder;
 
 use crate::account::Account;
 use crate::types::date::Date;
+use crate::util::escape_string;
 /// # Document Directive
 ///
 /// The Document directive attaches external files to specific accounts in the journal.
@@ -38,7 +41,7 @@ use crate::types::date::Date;
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.w1ins9jk4mq3>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Document {
+pub struct Document<'a> {
     /// Date the document was linked.
     pub date: Date,
 
@@ -46,5 +49,36 @@ pub struct Document {
     pub account: Account,
 
     /// Filesystem path to the document.
-    pub path: String,
+    #[builder(setter(into))]
+    pub path: Cow<'a, str>,
+}
+
+impl core::fmt::Display for Document<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{} document {} \"{}\"",
+            self.date,
+            self.account,
+            escape_string(&self.path)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let document = Document::builder()
+            .date(Date::from_str_unchecked("2013-11-03"))
+            .account(Account::from("Liabilities:CreditCard"))
+            .path("/home/joe/stmts/apr-2014.pdf".to_string())
+            .build();
+        assert_eq!(
+            document.to_string(),
+            r#"2013-11-03 document Liabilities:CreditCard "/home/joe/stmts/apr-2014.pdf""#
+        );
+    }
 }
@@ -1,4 +1,7 @@
+use alloc::borrow::Cow;
+
 use crate::types::date::Date;
+use crate::util::escape_string;
 use typed_builder::TypedBuilder;
 
 /// # Event Directive
@@ -37,13 +40,45 @@ use typed_builder::TypedBuilder;
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.tm5fxddlik5x>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Event {
+pub struct Event<'a> {
     /// Date the event occurred.
     pub date: Date,
 
     /// Name of the event.
-    pub name: String,
+    #[builder(setter(into))]
+    pub name: Cow<'a, str>,
 
     /// New value of the event.
-    pub description: String,
+    #[builder(setter(into))]
+    pub description: Cow<'a, str>,
+}
+
+impl core::fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{} event \"{}\" \"{}\"",
+            self.date,
+            escape_string(&self.name),
+            escape_string(&self.description)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let event = Event::builder()
+            .date(Date::from_str_unchecked("2014-07-09"))
+            .name("location".to_string())
+            .description("Paris, France".to_string())
+            .build();
+        assert_eq!(
+            event.to_string(),
+            r#"2014-07-09 event "location" "Paris, France""#
+        );
+    }
 }
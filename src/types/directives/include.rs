@@ -1,3 +1,9 @@
+use alloc::borrow::Cow;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+#[cfg(feature = "std")]
+use std::path::{Component, Path, PathBuf};
+
 use typed_builder::TypedBuilder;
 
 /// # Include Directive
@@ -24,22 +30,84 @@ use typed_builder::TypedBuilder;
 /// - Options handling may be subject to change in future versions.
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.86lelow4097r>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Include {
+pub struct Include<'a> {
     /// Fully qualified filename, including any necessary path segments.
-    pub filename: String,
+    #[builder(setter(into))]
+    pub filename: Cow<'a, str>,
 
     /// Source string from the parsed input
     #[builder(default)]
-    pub source: Option<String>,
+    pub source: Option<Cow<'a, str>>,
 }
 
 
-impl std::fmt::Display for Include {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Renders the source text of the include when it is known, and the filename otherwise.
+impl core::fmt::Display for Include<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if let Some(source) = &self.source {
+            return f.write_str(source);
+        }
         write!(f, "include {}", self.filename)
     }
 }
 
+/// Checks that following the includes from `root` never leads back to a file being included.
+/// `read` returns the filenames included by a file, so any parser can be used; relative
+/// filenames are resolved from the directory of the including file. On a cycle, returns the
+/// files along it, starting and ending with the same file.
+#[cfg(feature = "std")]
+pub fn detect_include_cycles(
+    root: &Path,
+    read: impl Fn(&Path) -> Vec<String>,
+) -> Result<(), Vec<PathBuf>> {
+    fn visit(
+        path: PathBuf,
+        read: &dyn Fn(&Path) -> Vec<String>,
+        stack: &mut Vec<PathBuf>,
+        done: &mut HashSet<PathBuf>,
+    ) -> Result<(), Vec<PathBuf>> {
+        if let Some(start) = stack.iter().position(|p| *p == path) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(path);
+            return Err(cycle);
+        }
+        if done.contains(&path) {
+            return Ok(());
+        }
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let filenames = read(&path);
+        stack.push(path);
+        for filename in filenames {
+            visit(normalize(&dir.join(filename)), read, stack, done)?;
+        }
+        done.extend(stack.pop());
+        Ok(())
+    }
+
+    visit(normalize(root), &read, &mut Vec::new(), &mut HashSet::new())
+}
+
+/// Resolves the `.` and `..` components of `path` lexically, so that the same file reached
+/// through different relative paths compares equal. Leading `..` components are kept.
+#[cfg(feature = "std")]
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match res.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    res.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => res.push(".."),
+            },
+            component => res.push(component),
+        }
+    }
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,4 +117,47 @@ mod tests {
         let include = Include::builder().filename("path/to/include/file.beancount".to_string()).build();
         assert_eq!(include.to_string(), "include path/to/include/file.beancount");
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_include_cycles() {
+        let read = |path: &Path| match path.to_str().unwrap() {
+            "books/main.beancount" => {
+                vec!["2014.beancount".to_string(), "prices.beancount".to_string()]
+            }
+            "books/2014.beancount" => vec!["./prices.beancount".to_string()],
+            "books/prices.beancount" => vec![],
+            "books/a.beancount" => vec!["b.beancount".to_string()],
+            "books/b.beancount" => vec!["a.beancount".to_string()],
+            path => panic!("unexpected read of {}", path),
+        };
+        assert_eq!(
+            detect_include_cycles(Path::new("books/main.beancount"), read),
+            Ok(())
+        );
+        assert_eq!(
+            detect_include_cycles(Path::new("books/a.beancount"), read),
+            Err(vec![
+                PathBuf::from("books/a.beancount"),
+                PathBuf::from("books/b.beancount"),
+                PathBuf::from("books/a.beancount"),
+            ])
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_detect_include_cycles_through_parent_dir() {
+        let read = |path: &Path| match path.to_str().unwrap() {
+            "books/a.beancount" => vec!["../books/./a.beancount".to_string()],
+            path => panic!("unexpected read of {}", path),
+        };
+        assert_eq!(
+            detect_include_cycles(Path::new("books/a.beancount"), read),
+            Err(vec![
+                PathBuf::from("books/a.beancount"),
+                PathBuf::from("books/a.beancount"),
+            ])
+        );
+    }
+}
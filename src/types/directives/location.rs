@@ -0,0 +1,108 @@
+use alloc::string::String;
+use core::{fmt, ops};
+
+use typed_builder::TypedBuilder;
+
+/// A position in a beancount source file, e.g. to point an editor at the directive an error is
+/// about. Lines and columns start at 1.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, TypedBuilder)]
+pub struct SourceLoc {
+    /// Path of the file, as given to the parser.
+    #[builder(setter(into))]
+    pub file: String,
+
+    pub line: usize,
+
+    pub column: usize,
+}
+
+/// Renders the location as `file:line:column`, as compilers do.
+impl fmt::Display for SourceLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A value, usually a [Directive](super::Directive), with the location it was parsed from.
+///
+/// The location is kept beside the value rather than in it, so that directives built in code
+/// need none and compare equal regardless of where they were read. The wrapper dereferences to
+/// the value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Located<T> {
+    pub value: T,
+    pub location: Option<SourceLoc>,
+}
+
+impl<T> Located<T> {
+    pub fn new(value: T, location: SourceLoc) -> Located<T> {
+        Located {
+            value,
+            location: Some(location),
+        }
+    }
+
+    /// Wraps a value that was not read from a file.
+    pub fn unlocated(value: T) -> Located<T> {
+        Located {
+            value,
+            location: None,
+        }
+    }
+
+    pub fn location(&self) -> Option<&SourceLoc> {
+        self.location.as_ref()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> ops::Deref for Located<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> ops::DerefMut for Located<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        account::Account,
+        date::Date,
+        directives::{open::Open, Directive},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_located_directive() {
+        let open = Open::builder()
+            .date(Date::from_str_unchecked("2014-05-01"))
+            .account(Account::from("Liabilities:CreditCard"))
+            .build();
+        let location = SourceLoc::builder()
+            .file("books/main.beancount")
+            .line(12)
+            .column(1)
+            .build();
+        let directive = Located::new(Directive::Open(open.clone()), location.clone());
+
+        assert_eq!(directive.location(), Some(&location));
+        assert_eq!(
+            directive.location().unwrap().to_string(),
+            "books/main.beancount:12:1"
+        );
+        assert_eq!(directive.type_name(), "open");
+        assert_eq!(directive.into_inner(), Directive::Open(open.clone()));
+        assert_eq!(Located::unlocated(Directive::Open(open)).location(), None);
+    }
+}
@@ -1,7 +1,25 @@
+use alloc::borrow::Cow;
+use core::{error, fmt};
+
+use crate::{date::Date, metadata::Meta};
+
 use self::{
-    balance::Balance, beancount_option::BcOption, close::Close, commodity::Commodity,
-    custom::Custom, document::Document, event::Event, include::Include, note::Note, open::Open,
-    pad::Pad, plugin::Plugin, prices::Price, query::Query, transaction::Transaction,
+    balance::Balance,
+    beancount_option::BcOption,
+    close::Close,
+    commodity::Commodity,
+    custom::Custom,
+    document::Document,
+    event::Event,
+    include::Include,
+    note::Note,
+    open::Open,
+    pad::Pad,
+    plugin::Plugin,
+    prices::Price,
+    pushmeta::{PopMeta, PushMeta},
+    query::Query,
+    transaction::Transaction,
 };
 
 pub mod balance;
@@ -12,6 +30,7 @@ pub mod custom;
 pub mod document;
 pub mod event;
 pub mod include;
+pub mod location;
 pub mod note;
 pub mod open;
 pub mod pad;
@@ -19,27 +38,565 @@ pub mod plugin;
 pub mod position;
 pub mod posting;
 pub mod prices;
+pub mod pushmeta;
 pub mod query;
 pub mod transaction;
 
+/// Error returned by the `validate` methods of directives.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DirectiveError {
+    /// A field that beancount requires to have text is empty or blank.
+    Blank(&'static str),
+}
+
+impl fmt::Display for DirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DirectiveError::Blank(field) => write!(f, "{} must not be blank", field),
+        }
+    }
+}
+
+impl error::Error for DirectiveError {}
+
 /// Enum of all directive types.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Directive {
-    Open(Open),
-    Close(Close),
-    Commodity(Commodity),
-    Transaction(Transaction),
-    Balance(Balance),
-    Pad(Pad),
-    Note(Note),
-    Document(Document),
-    Price(Price),
-    Event(Event),
-    Query(Query),
-    Custom(Custom),
+pub enum Directive<'a> {
+    Open(Open<'a>),
+    Close(Close<'a>),
+    Commodity(Commodity<'a>),
+    Transaction(Transaction<'a>),
+    Balance(Balance<'a>),
+    Pad(Pad<'a>),
+    Note(Note<'a>),
+    Document(Document<'a>),
+    Price(Price<'a>),
+    Event(Event<'a>),
+    Query(Query<'a>),
+    Custom(Custom<'a>),
     // other directives
-    Include(Include),
-    Option(BcOption),
-    Plugin(Plugin),
-    Unsupported,
+    Include(Include<'a>),
+    Option(BcOption<'a>),
+    Plugin(Plugin<'a>),
+    PushMeta(PushMeta),
+    PopMeta(PopMeta),
+    /// A directive this crate does not model, holding its raw text so that it is written back
+    /// unchanged.
+    Unsupported(Cow<'a, str>),
+}
+
+impl Directive<'_> {
+    /// Returns the directive as an [Entry], or `None` for unsupported directives.
+    pub fn as_entry(&self) -> Option<&dyn Entry> {
+        match self {
+            Directive::Open(d) => Some(d),
+            Directive::Close(d) => Some(d),
+            Directive::Commodity(d) => Some(d),
+            Directive::Transaction(d) => Some(d),
+            Directive::Balance(d) => Some(d),
+            Directive::Pad(d) => Some(d),
+            Directive::Note(d) => Some(d),
+            Directive::Document(d) => Some(d),
+            Directive::Price(d) => Some(d),
+            Directive::Event(d) => Some(d),
+            Directive::Query(d) => Some(d),
+            Directive::Custom(d) => Some(d),
+            Directive::Include(d) => Some(d),
+            Directive::Option(d) => Some(d),
+            Directive::Plugin(d) => Some(d),
+            Directive::PushMeta(d) => Some(d),
+            Directive::PopMeta(d) => Some(d),
+            Directive::Unsupported(_) => None,
+        }
+    }
+
+    /// Returns true if the directive is written with a date prefix, i.e. for every directive
+    /// except `option`, `plugin`, `include`, `pushmeta`, `popmeta` and unsupported ones.
+    pub fn is_dated(&self) -> bool {
+        !matches!(
+            self,
+            Directive::Option(_)
+                | Directive::Plugin(_)
+                | Directive::Include(_)
+                | Directive::PushMeta(_)
+                | Directive::PopMeta(_)
+                | Directive::Unsupported(_)
+        )
+    }
+
+    /// Returns the source text kept on options, plugins and includes from the parsed input, or
+    /// `None` for other directives and when it was not kept.
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            Directive::Option(d) => d.source.as_deref(),
+            Directive::Plugin(d) => d.source.as_deref(),
+            Directive::Include(d) => d.source.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the kind of the directive as used by beancount, e.g. `"txn"` for
+    /// transactions.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Directive::Open(_) => "open",
+            Directive::Close(_) => "close",
+            Directive::Commodity(_) => "commodity",
+            Directive::Transaction(_) => "txn",
+            Directive::Balance(_) => "balance",
+            Directive::Pad(_) => "pad",
+            Directive::Note(_) => "note",
+            Directive::Document(_) => "document",
+            Directive::Price(_) => "price",
+            Directive::Event(_) => "event",
+            Directive::Query(_) => "query",
+            Directive::Custom(_) => "custom",
+            Directive::Include(_) => "include",
+            Directive::Option(_) => "option",
+            Directive::Plugin(_) => "plugin",
+            Directive::PushMeta(_) => "pushmeta",
+            Directive::PopMeta(_) => "popmeta",
+            Directive::Unsupported(_) => "unsupported",
+        }
+    }
+
+    /// Calls the method of `visitor` for the kind of this directive.
+    pub fn accept(&self, visitor: &mut impl DirectiveVisitor) {
+        match self {
+            Directive::Open(d) => visitor.visit_open(d),
+            Directive::Close(d) => visitor.visit_close(d),
+            Directive::Commodity(d) => visitor.visit_commodity(d),
+            Directive::Transaction(d) => visitor.visit_transaction(d),
+            Directive::Balance(d) => visitor.visit_balance(d),
+            Directive::Pad(d) => visitor.visit_pad(d),
+            Directive::Note(d) => visitor.visit_note(d),
+            Directive::Document(d) => visitor.visit_document(d),
+            Directive::Price(d) => visitor.visit_price(d),
+            Directive::Event(d) => visitor.visit_event(d),
+            Directive::Query(d) => visitor.visit_query(d),
+            Directive::Custom(d) => visitor.visit_custom(d),
+            Directive::Include(d) => visitor.visit_include(d),
+            Directive::Option(d) => visitor.visit_option(d),
+            Directive::Plugin(d) => visitor.visit_plugin(d),
+            Directive::PushMeta(d) => visitor.visit_push_meta(d),
+            Directive::PopMeta(d) => visitor.visit_pop_meta(d),
+            Directive::Unsupported(raw) => visitor.visit_unsupported(raw),
+        }
+    }
+
+    /// Calls the method of `visitor` for the kind of this directive, which it may modify.
+    pub fn accept_mut(&mut self, visitor: &mut impl DirectiveVisitorMut) {
+        match self {
+            Directive::Open(d) => visitor.visit_open(d),
+            Directive::Close(d) => visitor.visit_close(d),
+            Directive::Commodity(d) => visitor.visit_commodity(d),
+            Directive::Transaction(d) => visitor.visit_transaction(d),
+            Directive::Balance(d) => visitor.visit_balance(d),
+            Directive::Pad(d) => visitor.visit_pad(d),
+            Directive::Note(d) => visitor.visit_note(d),
+            Directive::Document(d) => visitor.visit_document(d),
+            Directive::Price(d) => visitor.visit_price(d),
+            Directive::Event(d) => visitor.visit_event(d),
+            Directive::Query(d) => visitor.visit_query(d),
+            Directive::Custom(d) => visitor.visit_custom(d),
+            Directive::Include(d) => visitor.visit_include(d),
+            Directive::Option(d) => visitor.visit_option(d),
+            Directive::Plugin(d) => visitor.visit_plugin(d),
+            Directive::PushMeta(d) => visitor.visit_push_meta(d),
+            Directive::PopMeta(d) => visitor.visit_pop_meta(d),
+            Directive::Unsupported(raw) => visitor.visit_unsupported(raw),
+        }
+    }
+}
+
+impl fmt::Display for Directive<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Directive::Open(d) => d.fmt(f),
+            Directive::Close(d) => d.fmt(f),
+            Directive::Commodity(d) => d.fmt(f),
+            Directive::Transaction(d) => d.fmt(f),
+            Directive::Balance(d) => d.fmt(f),
+            Directive::Pad(d) => d.fmt(f),
+            Directive::Note(d) => d.fmt(f),
+            Directive::Document(d) => d.fmt(f),
+            Directive::Price(d) => d.fmt(f),
+            Directive::Event(d) => d.fmt(f),
+            Directive::Query(d) => d.fmt(f),
+            Directive::Custom(d) => d.fmt(f),
+            Directive::Include(d) => d.fmt(f),
+            Directive::Option(d) => d.fmt(f),
+            Directive::Plugin(d) => d.fmt(f),
+            Directive::PushMeta(d) => d.fmt(f),
+            Directive::PopMeta(d) => d.fmt(f),
+            Directive::Unsupported(raw) => f.write_str(raw),
+        }
+    }
+}
+
+/// Visits the directives of a ledger, e.g. with `Ledger::accept`, one method per kind of
+/// directive. Every method does nothing by default, so a visitor only implements the directives
+/// it cares about.
+pub trait DirectiveVisitor {
+    fn visit_open(&mut self, _: &Open<'_>) {}
+    fn visit_close(&mut self, _: &Close<'_>) {}
+    fn visit_commodity(&mut self, _: &Commodity<'_>) {}
+    fn visit_transaction(&mut self, _: &Transaction<'_>) {}
+    fn visit_balance(&mut self, _: &Balance<'_>) {}
+    fn visit_pad(&mut self, _: &Pad<'_>) {}
+    fn visit_note(&mut self, _: &Note<'_>) {}
+    fn visit_document(&mut self, _: &Document<'_>) {}
+    fn visit_price(&mut self, _: &Price<'_>) {}
+    fn visit_event(&mut self, _: &Event<'_>) {}
+    fn visit_query(&mut self, _: &Query<'_>) {}
+    fn visit_custom(&mut self, _: &Custom<'_>) {}
+    fn visit_include(&mut self, _: &Include<'_>) {}
+    fn visit_option(&mut self, _: &BcOption<'_>) {}
+    fn visit_plugin(&mut self, _: &Plugin<'_>) {}
+    fn visit_push_meta(&mut self, _: &PushMeta) {}
+    fn visit_pop_meta(&mut self, _: &PopMeta) {}
+    fn visit_unsupported(&mut self, _: &str) {}
+}
+
+/// Like [DirectiveVisitor], but with mutable access to rewrite the directives in place, e.g.
+/// with `Ledger::accept_mut`.
+pub trait DirectiveVisitorMut {
+    fn visit_open(&mut self, _: &mut Open<'_>) {}
+    fn visit_close(&mut self, _: &mut Close<'_>) {}
+    fn visit_commodity(&mut self, _: &mut Commodity<'_>) {}
+    fn visit_transaction(&mut self, _: &mut Transaction<'_>) {}
+    fn visit_balance(&mut self, _: &mut Balance<'_>) {}
+    fn visit_pad(&mut self, _: &mut Pad<'_>) {}
+    fn visit_note(&mut self, _: &mut Note<'_>) {}
+    fn visit_document(&mut self, _: &mut Document<'_>) {}
+    fn visit_price(&mut self, _: &mut Price<'_>) {}
+    fn visit_event(&mut self, _: &mut Event<'_>) {}
+    fn visit_query(&mut self, _: &mut Query<'_>) {}
+    fn visit_custom(&mut self, _: &mut Custom<'_>) {}
+    fn visit_include(&mut self, _: &mut Include<'_>) {}
+    fn visit_option(&mut self, _: &mut BcOption<'_>) {}
+    fn visit_plugin(&mut self, _: &mut Plugin<'_>) {}
+    fn visit_push_meta(&mut self, _: &mut PushMeta) {}
+    fn visit_pop_meta(&mut self, _: &mut PopMeta) {}
+    fn visit_unsupported(&mut self, _: &mut Cow<'_, str>) {}
+}
+
+/// Behaviour shared by all directives, for processing them generically without matching on
+/// [Directive].
+pub trait Entry: fmt::Display {
+    /// The date of the directive, or `None` for the undated `include`, `option`, `plugin`,
+    /// `pushmeta` and `popmeta`.
+    fn date(&self) -> Option<&Date>;
+
+    /// The metadata attached to the directive, if it carries any.
+    fn meta(&self) -> Option<&Meta> {
+        None
+    }
+}
+
+impl Entry for Open<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Close<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Commodity<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+
+    fn meta(&self) -> Option<&Meta> {
+        Some(&self.meta)
+    }
+}
+
+impl Entry for Transaction<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+
+    fn meta(&self) -> Option<&Meta> {
+        Some(&self.meta)
+    }
+}
+
+impl Entry for Balance<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Pad<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Note<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Document<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Price<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Event<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Query<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Custom<'_> {
+    fn date(&self) -> Option<&Date> {
+        Some(&self.date)
+    }
+}
+
+impl Entry for Include<'_> {
+    fn date(&self) -> Option<&Date> {
+        None
+    }
+}
+
+impl Entry for BcOption<'_> {
+    fn date(&self) -> Option<&Date> {
+        None
+    }
+}
+
+impl Entry for Plugin<'_> {
+    fn date(&self) -> Option<&Date> {
+        None
+    }
+}
+
+impl Entry for PushMeta {
+    fn date(&self) -> Option<&Date> {
+        None
+    }
+}
+
+impl Entry for PopMeta {
+    fn date(&self) -> Option<&Date> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use rust_decimal::Decimal;
+
+    use crate::{account::Account, amount::Amount};
+
+    use super::*;
+
+    const INPUT: &str =
+        "Called about fraudulent card.|/home/joe/stmts/apr-2014.pdf|location|Paris, France|\
+         france-balances|SELECT account|budget|TRUE|title|Ed's Ledger|beancount.plugins.auto|\
+         accounts.beancount|Lamb tagine";
+
+    /// One directive of each supported kind, with the text fields borrowed from `text`.
+    fn all_directives<'a>(text: &[&'a str]) -> Vec<Directive<'a>> {
+        let date = || Date::from_str_unchecked("2014-07-09");
+        let account = || Account::from("Liabilities:CreditCard");
+        let amount = || Amount::from((Decimal::new(3745, 2), "USD"));
+
+        vec![
+            Directive::Open(Open::builder().date(date()).account(account()).build()),
+            Directive::Close(Close::builder().date(date()).account(account()).build()),
+            Directive::Commodity(Commodity::builder().date(date()).name("USD".into()).build()),
+            Directive::Transaction(
+                Transaction::builder()
+                    .date(date())
+                    .narration(text[12])
+                    .build(),
+            ),
+            Directive::Balance(
+                Balance::builder()
+                    .date(date())
+                    .account(account())
+                    .amount(amount())
+                    .build(),
+            ),
+            Directive::Pad(
+                Pad::builder()
+                    .date(date())
+                    .pad_to_account(account())
+                    .pad_from_account(Account::from("Equity:Opening-Balances"))
+                    .build(),
+            ),
+            Directive::Note(
+                Note::builder()
+                    .date(date())
+                    .account(account())
+                    .comment(text[0])
+                    .build(),
+            ),
+            Directive::Document(
+                Document::builder()
+                    .date(date())
+                    .account(account())
+                    .path(text[1])
+                    .build(),
+            ),
+            Directive::Price(
+                Price::builder()
+                    .date(date())
+                    .currency("HOOL".into())
+                    .amount(amount())
+                    .build(),
+            ),
+            Directive::Event(
+                Event::builder()
+                    .date(date())
+                    .name(text[2])
+                    .description(text[3])
+                    .build(),
+            ),
+            Directive::Query(
+                Query::builder()
+                    .date(date())
+                    .name(text[4])
+                    .query_string(text[5])
+                    .build(),
+            ),
+            Directive::Custom(
+                Custom::builder()
+                    .date(date())
+                    .name(text[6])
+                    .args(vec![Cow::Borrowed(text[7])])
+                    .build(),
+            ),
+            Directive::Option(BcOption::builder().name(text[8]).val(text[9]).build()),
+            Directive::Plugin(Plugin::builder().module(text[10]).build()),
+            Directive::Include(Include::builder().filename(text[11]).build()),
+        ]
+    }
+
+    #[test]
+    fn test_directives_borrow_input() {
+        let input = String::from(INPUT);
+        let text: Vec<&str> = input.split('|').collect();
+        let directives = all_directives(&text);
+
+        assert_eq!(directives.len(), 15);
+        let borrowed = |s: &Cow<str>| matches!(s, Cow::Borrowed(_));
+        for directive in &directives {
+            let all_borrowed = match directive {
+                Directive::Transaction(d) => borrowed(&d.narration),
+                Directive::Note(d) => borrowed(&d.comment),
+                Directive::Document(d) => borrowed(&d.path),
+                Directive::Event(d) => borrowed(&d.name) && borrowed(&d.description),
+                Directive::Query(d) => borrowed(&d.name) && borrowed(&d.query_string),
+                Directive::Custom(d) => borrowed(&d.name) && d.args.iter().all(borrowed),
+                Directive::Option(d) => borrowed(&d.name) && borrowed(&d.val),
+                Directive::Plugin(d) => borrowed(&d.module),
+                Directive::Include(d) => borrowed(&d.filename),
+                _ => true,
+            };
+            assert!(all_borrowed, "{:?}", directive);
+        }
+        assert_eq!(
+            directives[6].to_string(),
+            r#"2014-07-09 note Liabilities:CreditCard "Called about fraudulent card.""#
+        );
+    }
+
+    #[test]
+    fn test_is_dated() {
+        let open = Open::builder()
+            .date(Date::from_str_unchecked("2014-05-01"))
+            .account(Account::from("Liabilities:CreditCard"))
+            .build();
+        assert!(Directive::Open(open).is_dated());
+
+        let option = BcOption::builder().name("title").val("Ed's Ledger").build();
+        let plugin = Plugin::builder().module("beancount.plugins.auto").build();
+        let include = Include::builder().filename("accounts.beancount").build();
+        assert!(!Directive::Option(option).is_dated());
+        assert!(!Directive::Plugin(plugin).is_dated());
+        assert!(!Directive::Include(include).is_dated());
+        assert!(!Directive::Unsupported("".into()).is_dated());
+    }
+
+    #[test]
+    fn test_type_name() {
+        let text: Vec<&str> = INPUT.split('|').collect();
+        let names: Vec<&str> = all_directives(&text)
+            .iter()
+            .map(Directive::type_name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "open",
+                "close",
+                "commodity",
+                "txn",
+                "balance",
+                "pad",
+                "note",
+                "document",
+                "price",
+                "event",
+                "query",
+                "custom",
+                "option",
+                "plugin",
+                "include",
+            ]
+        );
+        assert_eq!(Directive::Unsupported("".into()).type_name(), "unsupported");
+    }
+
+    #[test]
+    fn test_display_unsupported() {
+        let raw = "2020-01-01 custom-weird Assets:Cash \"keep me\"";
+        let directive = Directive::Unsupported(raw.into());
+        assert!(matches!(
+            directive,
+            Directive::Unsupported(Cow::Borrowed(_))
+        ));
+        assert_eq!(directive.to_string(), raw);
+        assert!(directive.as_entry().is_none());
+    }
+
+    #[test]
+    fn test_source() {
+        let source = r#"include	"accounts.beancount""#;
+        let include = Include::builder()
+            .filename("accounts.beancount")
+            .source(Some(source.into()))
+            .build();
+        let directive = Directive::Include(include);
+        assert_eq!(directive.source(), Some(source));
+        assert_eq!(directive.to_string(), source);
+
+        let text: Vec<&str> = INPUT.split('|').collect();
+        assert!(all_directives(&text).iter().all(|d| d.source().is_none()));
+    }
 }
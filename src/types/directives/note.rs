@@ -1,7 +1,10 @@
+use alloc::borrow::Cow;
+
 use typed_builder::TypedBuilder;
 
 use crate::account::Account;
 use crate::types::date::Date;
+use crate::util::escape_string;
 
 /// # Note Directive
 ///
@@ -29,7 +32,7 @@ use crate::types::date::Date;
 /// - Provides additional context when reviewing account history.
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.c4cyaa6o6rqm>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Note {
+pub struct Note<'a> {
     /// Date of the note.
     pub date: Date,
 
@@ -37,13 +40,20 @@ pub struct Note {
     pub account: Account,
 
     /// Note description.
-    pub comment: String,
+    #[builder(setter(into))]
+    pub comment: Cow<'a, str>,
 }
 
 
-impl std::fmt::Display for Note {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{} note {} \"{}\"", self.date, self.account, self.comment)
+impl core::fmt::Display for Note<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{} note {} \"{}\"",
+            self.date,
+            self.account,
+            escape_string(&self.comment)
+        )
     }
 }
 
@@ -56,4 +66,17 @@ mod tests {
         let note = Note::builder().date(Date::from_str_unchecked("2013-11-03")).account(Account::from("Liabilities:CreditCard")).comment("Called about fraudulent card.".to_string()).build();
         assert_eq!(note.to_string(), r#"2013-11-03 note Liabilities:CreditCard "Called about fraudulent card.""#);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_display_escapes_comment() {
+        let note = Note::builder()
+            .date(Date::from_str_unchecked("2013-11-03"))
+            .account(Account::from("Liabilities:CreditCard"))
+            .comment(r#"Said "card stolen" at C:\branch"#.to_string())
+            .build();
+        assert_eq!(
+            note.to_string(),
+            r#"2013-11-03 note Liabilities:CreditCard "Said \"card stolen\" at C:\\branch""#
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use std::convert::TryFrom;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::{error, fmt};
 
 use typed_builder::TypedBuilder;
 
@@ -37,7 +41,7 @@ use crate::types::date::Date;
 /// - NONE: No lot matching is performed, accepts lots of any price.
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.omdgvaikswd0>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Open {
+pub struct Open<'a> {
     /// Date the account was opened.
     pub date: Date,
 
@@ -53,11 +57,35 @@ pub struct Open {
     /// [`Booking::Strict`](enum.Booking.html).
     #[builder(default)]
     pub booking: Option<Booking>,
+
+    /// Ties the directive to the lifetime of [Directive](super::Directive). It holds no borrowed
+    /// data, as all its fields are owned.
+    #[builder(default, setter(skip))]
+    phantom: PhantomData<&'a ()>,
 }
 
+impl<'a> Open<'a> {
+    /// Opens `account` on `date`, with no currency constraint or booking method. The date may be
+    /// anything convertible into a [Date], such as a `chrono::NaiveDate`.
+    pub fn on(date: impl Into<Date>, account: Account) -> Open<'a> {
+        Open::builder().date(date.into()).account(account).build()
+    }
+
+    /// Sets the booking method from its name as written in the input, e.g. `"FIFO"`.
+    /// Surrounding double quotes are stripped. Unknown methods leave the booking unchanged.
+    pub fn set_booking_from_str(&mut self, s: &str) -> Result<(), BookingMethodError> {
+        let s = s.trim();
+        let s = s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .unwrap_or(s);
+        self.booking = Some(Booking::try_from(s)?);
+        Ok(())
+    }
+}
 
-impl std::fmt::Display for Open {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Open<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{} open {} {}", self.date, self.account, self.currencies.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" "))
     }
 }
@@ -88,7 +116,7 @@ pub enum Booking {
 }
 
 impl TryFrom<&str> for Booking {
-    type Error = ();
+    type Error = BookingMethodError;
 
     fn try_from(val: &str) -> Result<Self, Self::Error> {
         match val {
@@ -98,11 +126,23 @@ impl TryFrom<&str> for Booking {
             "AVERAGE" => Ok(Booking::Average),
             "FIFO" => Ok(Booking::Fifo),
             "LIFO" => Ok(Booking::Lifo),
-            _ => Err(()),
+            _ => Err(BookingMethodError(val.to_string())),
         }
     }
 }
 
+/// Error returned when a string is not the name of a booking method.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BookingMethodError(pub String);
+
+impl fmt::Display for BookingMethodError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown booking method: {}", self.0)
+    }
+}
+
+impl error::Error for BookingMethodError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,5 +152,35 @@ mod tests {
         let open = Open::builder().date(Date::from_str_unchecked("2014-05-01")).account(Account::from("Liabilities:CreditCard:CapitalOne")).currencies(vec![Currency::from("USD")]).build();
         assert_eq!(open.to_string(), "2014-05-01 open Liabilities:CreditCard:CapitalOne USD");
     }
-}
 
+    #[test]
+    fn test_on() {
+        let date = chrono::NaiveDate::from_ymd_opt(2014, 5, 1).unwrap();
+        let open = Open::on(date, Account::from("Liabilities:CreditCard:CapitalOne"));
+        assert_eq!(
+            open,
+            Open::builder()
+                .date(Date::from_str_unchecked("2014-05-01"))
+                .account(Account::from("Liabilities:CreditCard:CapitalOne"))
+                .build()
+        );
+    }
+
+    #[test]
+    fn test_set_booking_from_str() {
+        let mut open = Open::builder()
+            .date(Date::from_str_unchecked("2014-05-01"))
+            .account(Account::from("Assets:ETrade:IVV"))
+            .build();
+        assert_eq!(open.set_booking_from_str(r#""FIFO""#), Ok(()));
+        assert_eq!(open.booking, Some(Booking::Fifo));
+        assert_eq!(open.set_booking_from_str("STRICT"), Ok(()));
+        assert_eq!(open.booking, Some(Booking::Strict));
+
+        assert_eq!(
+            open.set_booking_from_str(r#""RANDOM""#),
+            Err(BookingMethodError("RANDOM".to_string()))
+        );
+        assert_eq!(open.booking, Some(Booking::Strict));
+    }
+}
@@ -1,3 +1,5 @@
+use core::marker::PhantomData;
+
 use typed_builder::TypedBuilder;
 
 use crate::account::Account;
@@ -40,7 +42,7 @@ use crate::types::date::Date;
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.aw8ic3d8k8rq>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Pad {
+pub struct Pad<'a> {
     /// Date of the pad.
     pub date: Date,
 
@@ -49,11 +51,16 @@ pub struct Pad {
 
     /// Account to pad from.
     pub pad_from_account: Account,
+
+    /// Ties the directive to the lifetime of [Directive](super::Directive). It holds no borrowed
+    /// data, as all its fields are owned.
+    #[builder(default, setter(skip))]
+    phantom: PhantomData<&'a ()>,
 }
 
 
-impl std::fmt::Display for Pad {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Pad<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{} pad {} {}", self.date, self.pad_to_account, self.pad_from_account)
     }
 }
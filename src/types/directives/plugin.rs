@@ -1,3 +1,5 @@
+use alloc::borrow::Cow;
+
 use typed_builder::TypedBuilder;
 /// # Plugin Directive
 ///
@@ -27,24 +29,30 @@ use typed_builder::TypedBuilder;
 /// ## References
 /// - Refer to "Scripting & Plugins" documentation for detailed information.
 /// - Check individual plugin documentation for specific configuration options.
-
+///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.lxgs9ewvbt8k>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Plugin {
+pub struct Plugin<'a> {
     /// Full module name of the plugin.
-    pub module: String,
+    #[builder(setter(into))]
+    pub module: Cow<'a, str>,
 
     /// Configuration data to be passed to the plugin.
     #[builder(default)]
-    pub config: Option<String>,
+    pub config: Option<Cow<'a, str>>,
 
     /// Source string from the parsed input
     #[builder(default)]
-    pub source: Option<String>,
+    pub source: Option<Cow<'a, str>>,
 }
 
-impl std::fmt::Display for Plugin {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+/// Renders the source text of the plugin when it is known, and the module and configuration
+/// otherwise.
+impl core::fmt::Display for Plugin<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if let Some(source) = &self.source {
+            return f.write_str(source);
+        }
         write!(f, "plugin \"{}\" \"{}\" ", self.module, self.config.as_deref().unwrap_or_default())
     }
 }
@@ -55,7 +63,7 @@ mod tests {
 
     #[test]
     fn test_display() {
-        let plugin = Plugin::builder().module("beancount.plugins.module_name".to_string()).config(Some("configuration data".to_string())).build();
+        let plugin = Plugin::builder().module("beancount.plugins.module_name".to_string()).config(Some("configuration data".into())).build();
         assert_eq!(plugin.to_string(), r#"plugin "beancount.plugins.module_name" "configuration data" "#);
     }
 }
\ No newline at end of file
@@ -1,9 +1,17 @@
-use std::fmt;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::{cmp::Ordering, error, fmt, str::FromStr};
 
 use rust_decimal::Decimal;
 use typed_builder::TypedBuilder;
 
-use crate::{amount::Amount, currency::Currency, types::date::Date};
+use crate::{
+    amount::Amount,
+    currency::Currency,
+    types::date::Date,
+    util::{escape_string, unquote_string},
+};
 
 /// # Reducing Positions
 ///
@@ -114,6 +122,18 @@ pub struct Cost {
     pub label: Option<String>,
 }
 
+/// Renders the cost of a lot in beancount's `{...}` syntax, e.g.
+/// `{183.07 USD, 2014-02-11, "ref-001"}`.
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{{} {}, {}", self.number, self.currency, self.date)?;
+        if let Some(label) = &self.label {
+            write!(f, ", \"{}\"", escape_string(label))?;
+        }
+        write!(f, "}}")
+    }
+}
+
 // TODO: Important Note. Amounts specified as either per-share or total prices or costs are always
 // unsigned. It is an error to use a negative sign or a negative cost and Beancount will raise an
 // error if you attempt to do so.
@@ -141,14 +161,186 @@ pub struct CostSpec {
     pub merge_cost: bool,
 }
 
+impl CostSpec {
+    /// Returns the spec with `default` as its currency if it has none, as for the shorthand
+    /// `{100}` whose currency is inherited from the context of the posting.
+    pub fn with_default_currency(&self, default: &Currency) -> CostSpec {
+        CostSpec {
+            currency: self.currency.clone().or_else(|| Some(default.clone())),
+            ..self.clone()
+        }
+    }
+
+    /// Returns `true` if a lot held at `cost` matches the spec, i.e. the per-unit number,
+    /// currency, date and label of the spec are those of the lot where the spec gives them. An
+    /// empty spec `{}` matches any lot.
+    pub fn matches(&self, cost: &Cost) -> bool {
+        (self.number_per.is_none() || self.number_per == Some(cost.number))
+            && (self.currency.is_none() || self.currency.as_ref() == Some(&cost.currency))
+            && (self.date.is_none() || self.date.as_ref() == Some(&cost.date))
+            && (self.label.is_none() || self.label == cost.label)
+    }
+
+    /// Resolves the cost of a lot of `units` acquired on `date`: the per-unit cost, plus the
+    /// total cost spread over the units. The lot date defaults to `date` when the spec has none,
+    /// so this is only meant for augmentations; reductions take the cost of the lots they match,
+    /// see [Inventory::book](crate::inventory::Inventory::book).
+    ///
+    /// Returns `None` when the spec has no number or no currency, as is typical of reductions.
+    pub fn to_cost(&self, units: &Amount, date: &Date) -> Option<Cost> {
+        if self.number_per.is_none() && self.number_total.is_none() {
+            return None;
+        }
+        let mut number = self.number_per.unwrap_or_default();
+        if let Some(total) = self.number_total {
+            if units.num.is_zero() {
+                return None;
+            }
+            number += total / units.num.abs();
+        }
+        Some(Cost {
+            number,
+            currency: self.currency.clone()?,
+            date: self.date.clone().unwrap_or_else(|| date.clone()),
+            label: self.label.clone(),
+        })
+    }
+}
+
+/// Renders the cost spec in beancount's `{...}` syntax, e.g. `{183.07 USD, 2014-02-11, "ref-001"}`.
+/// A total cost is written after a `#`, as in `{# 1830.70 USD}`, and merging lots as `{*}`.
 impl fmt::Display for CostSpec {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut res = format!("{}", self.number_per.unwrap_or(Decimal::default()));
+        let mut components = Vec::new();
+
+        let mut amount = Vec::new();
+        if let Some(number_per) = self.number_per {
+            amount.push(number_per.to_string());
+        }
+        if let Some(number_total) = self.number_total {
+            amount.push(format!("# {}", number_total));
+        }
         if let Some(currency) = &self.currency {
-            res.push_str(&format!(" {}", currency));
+            amount.push(currency.to_string());
+        }
+        if !amount.is_empty() {
+            components.push(amount.join(" "));
+        }
+        if let Some(date) = &self.date {
+            components.push(date.to_string());
+        }
+        if let Some(label) = &self.label {
+            components.push(format!("\"{}\"", escape_string(label)));
+        }
+        if self.merge_cost {
+            components.push("*".to_string());
+        }
+
+        write!(f, "{{{}}}", components.join(", "))
+    }
+}
+
+/// Error returned when a string cannot be parsed as a `CostSpec`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CostSpecError(pub String);
+
+impl fmt::Display for CostSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cost specification: {}", self.0)
+    }
+}
+
+impl error::Error for CostSpecError {}
+
+/// Parses a cost spec in beancount's `{...}` syntax. The components are separated by commas and
+/// may appear in any order: a per-unit and/or total (`#`) cost with an optional currency, a date,
+/// a quoted label and the `*` merge marker. The `{{...}}` form is read as a total cost.
+impl FromStr for CostSpec {
+    type Err = CostSpecError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || CostSpecError(s.to_string());
+        let trimmed = s.trim();
+        let (inner, total) = if let Some(inner) = trimmed
+            .strip_prefix("{{")
+            .and_then(|t| t.strip_suffix("}}"))
+        {
+            (inner, true)
+        } else {
+            let inner = trimmed
+                .strip_prefix('{')
+                .and_then(|t| t.strip_suffix('}'))
+                .ok_or_else(err)?;
+            (inner, false)
+        };
+
+        let mut spec = CostSpec::builder().build();
+        for component in split_components(inner) {
+            let component = component.trim();
+            if component.is_empty() {
+                continue;
+            }
+            if component == "*" {
+                spec.merge_cost = true;
+            } else if component.len() >= 2 && component.starts_with('"') && component.ends_with('"')
+            {
+                spec.label = Some(unquote_string(component));
+            } else if let Ok(date) = component.parse::<Date>() {
+                spec.date = Some(date);
+            } else {
+                let tokens: Vec<&str> = component.split_whitespace().collect();
+                let (numbers, currency) = match tokens.split_last() {
+                    Some((last, rest))
+                        if Decimal::from_str(last).is_err() && !last.contains('#') =>
+                    {
+                        (rest.join(" "), Some(last.to_string()))
+                    }
+                    _ => (tokens.join(" "), None),
+                };
+                let parse = |n: &str| -> Result<Option<Decimal>, CostSpecError> {
+                    let n = n.trim();
+                    if n.is_empty() {
+                        Ok(None)
+                    } else {
+                        Decimal::from_str(n).map(Some).map_err(|_| err())
+                    }
+                };
+                match numbers.split_once('#') {
+                    Some(_) if total => return Err(err()),
+                    Some((per, tot)) => {
+                        spec.number_per = parse(per)?;
+                        spec.number_total = parse(tot)?;
+                    }
+                    None if total => spec.number_total = parse(&numbers)?,
+                    None => spec.number_per = parse(&numbers)?,
+                }
+                spec.currency = currency;
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// Splits the inside of a cost spec on commas that are not part of a quoted label.
+fn split_components(s: &str) -> Vec<&str> {
+    let mut components = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                components.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
         }
-        write!(f, "{}", res)
     }
+    components.push(&s[start..]);
+    components
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, TypedBuilder)]
@@ -156,3 +348,186 @@ pub struct Position {
     pub units: Amount,
     pub cost: Option<Cost>,
 }
+
+impl Position {
+    /// Returns the acquisition date of the lot, or `None` for a position not held at cost.
+    pub fn cost_date(&self) -> Option<&Date> {
+        self.cost.as_ref().map(|c| &c.date)
+    }
+
+    /// Orders positions by the date of their lot, oldest first, with the positions not held at
+    /// cost last. Sorting with it gives the order in which `FIFO` reduces lots; reversing the lots
+    /// held at cost gives `LIFO`.
+    pub fn cmp_by_date(&self, other: &Position) -> Ordering {
+        match (self.cost_date(), other.cost_date()) {
+            (Some(a), Some(b)) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// Renders the units followed by the cost, if any, e.g. `20 IVV {183.07 USD, 2014-02-11}`.
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.units)?;
+        if let Some(cost) = &self.cost {
+            write!(f, " {}", cost)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_spec_merge_cost() {
+        let spec: CostSpec = "{*}".parse().unwrap();
+        assert_eq!(spec, CostSpec::builder().merge_cost(true).build());
+        assert_eq!(spec.number_per, None);
+        assert_eq!(spec.number_total, None);
+        assert_eq!(spec.currency, None);
+        assert_eq!(spec.date, None);
+        assert_eq!(spec.label, None);
+        assert_eq!(spec.to_string(), "{*}");
+    }
+
+    #[test]
+    fn test_cost_spec_round_trip() {
+        for s in [
+            "{}",
+            "{183.07 USD}",
+            "{# 1830.70 USD}",
+            "{183.07 # 9.95 USD}",
+            "{183.07 USD, 2014-02-11, \"ref-001\"}",
+            "{2014-02-11}",
+            "{\"ref-001\"}",
+            "{100}",
+            "{183.07 USD, *}",
+        ] {
+            assert_eq!(s.parse::<CostSpec>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_cost_spec_escaped_label() {
+        let s = r#"{183.07 USD, "ref \"001\", IVV"}"#;
+        let spec: CostSpec = s.parse().unwrap();
+        assert_eq!(spec.label.as_deref(), Some(r#"ref "001", IVV"#));
+        assert_eq!(spec.to_string(), s);
+    }
+
+    #[test]
+    fn test_cost_spec_from_str() {
+        let spec: CostSpec = r#"{"ref, 001", 183.07 USD}"#.parse().unwrap();
+        assert_eq!(spec.number_per, Some(Decimal::from_str("183.07").unwrap()));
+        assert_eq!(spec.currency, Some(Currency::from("USD")));
+        assert_eq!(spec.label, Some("ref, 001".to_string()));
+
+        let spec: CostSpec = "{{1830.70 USD}}".parse().unwrap();
+        assert_eq!(spec.number_per, None);
+        assert_eq!(
+            spec.number_total,
+            Some(Decimal::from_str("1830.70").unwrap())
+        );
+
+        assert!("183.07 USD".parse::<CostSpec>().is_err());
+        assert!("{abc def USD}".parse::<CostSpec>().is_err());
+    }
+
+    #[test]
+    fn test_cost_spec_to_cost() {
+        let units = Amount::from((Decimal::from(10), "IVV"));
+        let date = Date::from_str_unchecked("2014-02-11");
+        let spec: CostSpec = "{183.07 # 9.95 USD, \"ref-001\"}".parse().unwrap();
+        assert_eq!(
+            spec.to_cost(&units, &date),
+            Some(Cost {
+                number: Decimal::new(184065, 3),
+                currency: "USD".to_string(),
+                date: date.clone(),
+                label: Some("ref-001".to_string()),
+            })
+        );
+
+        let spec: CostSpec = "{{1830.70 USD, 2014-02-10}}".parse().unwrap();
+        let cost = spec.to_cost(&units, &date).unwrap();
+        assert_eq!(cost.number, Decimal::new(18307, 2));
+        assert_eq!(cost.date, Date::from_str_unchecked("2014-02-10"));
+
+        assert_eq!(CostSpec::builder().build().to_cost(&units, &date), None);
+    }
+
+    #[test]
+    fn test_cost_spec_matches() {
+        let cost = Cost {
+            number: Decimal::new(18307, 2),
+            currency: "USD".to_string(),
+            date: Date::from_str_unchecked("2014-02-11"),
+            label: Some("ref-001".to_string()),
+        };
+        let matches = |spec: &str| spec.parse::<CostSpec>().unwrap().matches(&cost);
+        assert!(matches("{}"));
+        assert!(matches("{183.07 USD}"));
+        assert!(matches("{2014-02-11, \"ref-001\"}"));
+        assert!(!matches("{187.12 USD}"));
+        assert!(!matches("{183.07 EUR}"));
+        assert!(!matches("{2014-03-22}"));
+        assert!(!matches("{\"ref-002\"}"));
+    }
+
+    #[test]
+    fn test_cost_spec_with_default_currency() {
+        let spec: CostSpec = "{100}".parse().unwrap();
+        assert_eq!(spec.number_per, Some(Decimal::from(100)));
+        assert_eq!(spec.currency, None);
+        assert_eq!(spec.to_string(), "{100}");
+
+        let usd = Currency::from("USD");
+        let filled = spec.with_default_currency(&usd);
+        assert_eq!(filled.currency, Some(usd.clone()));
+        assert_eq!(filled.to_string(), "{100 USD}");
+
+        let eur: CostSpec = "{100 EUR}".parse().unwrap();
+        assert_eq!(eur.with_default_currency(&usd), eur);
+    }
+
+    #[test]
+    fn test_cmp_by_date() {
+        let lot = |units: &str, date: &str| Position {
+            units: units.parse().unwrap(),
+            cost: Some(Cost {
+                number: Decimal::new(18307, 2),
+                currency: "USD".to_string(),
+                date: Date::from_str_unchecked(date),
+                label: None,
+            }),
+        };
+        let cash = Position {
+            units: "100 USD".parse().unwrap(),
+            cost: None,
+        };
+        let mut positions = [
+            lot("15 IVV", "2014-03-22"),
+            cash.clone(),
+            lot("20 IVV", "2014-02-11"),
+            lot("5 IVV", "2014-04-01"),
+        ];
+        positions.sort_by(Position::cmp_by_date);
+
+        let dates: Vec<Option<&Date>> = positions.iter().map(Position::cost_date).collect();
+        assert_eq!(
+            dates,
+            vec![
+                Some(&Date::from_str_unchecked("2014-02-11")),
+                Some(&Date::from_str_unchecked("2014-03-22")),
+                Some(&Date::from_str_unchecked("2014-04-01")),
+                None,
+            ]
+        );
+        assert_eq!(positions[3], cash);
+    }
+}
@@ -1,9 +1,11 @@
-use std::fmt;
+use core::convert::TryFrom;
+use core::{error, fmt};
 
 use typed_builder::TypedBuilder;
 
 use crate::account::Account;
-use crate::amount::IncompleteAmount;
+use crate::amount::{Amount, IncompleteAmount};
+use crate::currency::Currency;
 use crate::flags::Flag;
 use crate::metadata::Meta;
 
@@ -84,21 +86,239 @@ pub struct Posting {
     pub meta: Meta,
 }
 
+impl Posting {
+    /// Builds a posting of a complete amount to `account`, with no cost, price or flag.
+    pub fn from_amount(account: Account, amount: Amount) -> Posting {
+        Posting::builder()
+            .account(account)
+            .units(IncompleteAmount::from(amount))
+            .build()
+    }
+
+    /// Returns the weight of the posting, the amount used to check that a transaction balances:
+    ///
+    /// 1. With a cost, the units times the per-unit cost, plus the total cost when one is given
+    ///    with `#`. A total cost alone, as in `{# 1830.70 USD}`, is the weight as is, signed like
+    ///    the units.
+    /// 2. With a price and no cost, the units times the price.
+    /// 3. Otherwise, the units themselves.
+    ///
+    /// Returns `None` when the units, or the cost or price that applies, are incomplete.
+    pub fn weight(&self) -> Option<Amount> {
+        let units = Amount::try_from(self.units.clone()).ok()?;
+        if let Some(cost) = &self.cost {
+            if cost.number_per.is_none() && cost.number_total.is_none() {
+                return None;
+            }
+            let mut num = units.num * cost.number_per.unwrap_or_default();
+            if let Some(total) = cost.number_total {
+                num += if units.num.is_sign_negative() {
+                    -total
+                } else {
+                    total
+                };
+            }
+            return Some(Amount {
+                num,
+                currency: cost.currency.clone()?,
+            });
+        }
+        if let Some(price) = &self.price {
+            let price = Amount::try_from(price.clone()).ok()?;
+            return Some(Amount {
+                num: units.num * price.num,
+                currency: price.currency,
+            });
+        }
+        Some(units)
+    }
+
+    /// Returns the per-unit cost as the price of a posting held at cost without a price, e.g.
+    /// `183.07 USD` for `10 IVV {183.07 USD}`. A total cost is spread over the units. Returns
+    /// `None` when the posting has a price or no cost.
+    pub fn implied_price(&self) -> Option<IncompleteAmount> {
+        if self.price.is_some() {
+            return None;
+        }
+        let cost = self.cost.as_ref()?;
+        let mut num = cost.number_per;
+        if let Some(total) = cost.number_total {
+            let units = self.units.num.filter(|n| !n.is_zero())?;
+            num = Some(num.unwrap_or_default() + total / units.abs());
+        }
+        Some(
+            IncompleteAmount::builder()
+                .num(num)
+                .currency(cost.currency.clone())
+                .build(),
+        )
+    }
+
+    /// Checks that the cost and the price of the posting are in the same currency. When a lot
+    /// held at cost is sold, beancount expects the price to be quoted in the cost currency, so a
+    /// mismatch is usually a typo.
+    pub fn currency_consistency(&self) -> Result<(), PostingConsistencyError> {
+        let cost_currency = self.cost.as_ref().and_then(|c| c.currency.as_ref());
+        let price_currency = self.price.as_ref().and_then(|p| p.currency.as_ref());
+        match (cost_currency, price_currency) {
+            (Some(cost), Some(price)) if cost != price => Err(
+                PostingConsistencyError::CurrencyMismatch(cost.clone(), price.clone()),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Error returned by [Posting::currency_consistency].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PostingConsistencyError {
+    /// The cost and price currencies differ, in that order.
+    CurrencyMismatch(Currency, Currency),
+}
+
+impl fmt::Display for PostingConsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PostingConsistencyError::CurrencyMismatch(cost, price) => write!(
+                f,
+                "cost currency {} does not match price currency {}",
+                cost, price
+            ),
+        }
+    }
+}
+
+impl error::Error for PostingConsistencyError {}
+
 impl fmt::Display for Posting {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // 使用制表符分隔账户和单位
-        let mut res = format!("{}\t{}", self.account, self.units);
+        write!(f, "{}\t{}", self.account, self.units)?;
 
         // 添加成本信息（如果有）
         if let Some(cost) = &self.cost {
-            res.push_str(&format!("\t{}", cost));
+            write!(f, "\t{}", cost)?;
         }
 
         // 添加价格信息（如果有）
         if let Some(price) = &self.price {
-            res.push_str(&format!("\t@ {}", price));
+            write!(f, "\t@ {}", price)?;
         }
 
-        write!(f, "{}", res)
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    fn posting(units: &str, cost: Option<&str>, price: Option<&str>) -> Posting {
+        let amount = |s: &str| IncompleteAmount::from(s.parse::<Amount>().unwrap());
+        Posting::builder()
+            .account(Account::from("Assets:ETrade:IVV"))
+            .units(amount(units))
+            .cost(cost.map(|c| c.parse().unwrap()))
+            .price(price.map(amount))
+            .build()
+    }
+
+    fn amount(s: &str) -> Amount {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_weight() {
+        assert_eq!(
+            posting("-400.00 USD", None, None).weight(),
+            Some(amount("-400.00 USD"))
+        );
+        assert_eq!(
+            posting("-400.00 USD", None, Some("1.09 CAD")).weight(),
+            Some(amount("-436.0000 CAD"))
+        );
+        assert_eq!(
+            posting("10 IVV", Some("{183.07 USD}"), None).weight(),
+            Some(amount("1830.70 USD"))
+        );
+        assert_eq!(
+            posting("-10 IVV", Some("{183.07 USD}"), Some("197.90 USD")).weight(),
+            Some(amount("-1830.70 USD"))
+        );
+        assert_eq!(posting("10 IVV", Some("{}"), None).weight(), None);
+    }
+
+    #[test]
+    fn test_weight_with_total_cost() {
+        let weight = posting("10 IVV", Some("{# 1830.70 USD}"), None)
+            .weight()
+            .unwrap();
+        assert_eq!(weight, amount("1830.70 USD"));
+        assert_eq!(weight.num, Decimal::from_str("1830.70").unwrap());
+
+        assert_eq!(
+            posting("-10 IVV", Some("{# 1830.70 USD}"), None).weight(),
+            Some(amount("-1830.70 USD"))
+        );
+        assert_eq!(
+            posting("10 IVV", Some("{183.00 # 0.70 USD}"), None).weight(),
+            Some(amount("1830.70 USD"))
+        );
+    }
+
+    #[test]
+    fn test_currency_consistency() {
+        assert_eq!(
+            posting("-10 IVV", Some("{183.07 USD}"), Some("197.90 USD")).currency_consistency(),
+            Ok(())
+        );
+        assert_eq!(
+            posting("-400.00 USD", None, Some("1.09 CAD")).currency_consistency(),
+            Ok(())
+        );
+        assert_eq!(
+            posting("-10 IVV", Some("{183.07 USD}"), Some("197.90 EUR")).currency_consistency(),
+            Err(PostingConsistencyError::CurrencyMismatch(
+                "USD".to_string(),
+                "EUR".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_implied_price() {
+        assert_eq!(
+            posting("10 IVV", Some("{183.07 USD}"), None).implied_price(),
+            Some(IncompleteAmount::from(amount("183.07 USD")))
+        );
+        assert_eq!(
+            posting("10 IVV", Some("{# 1830.70 USD}"), None).implied_price(),
+            Some(IncompleteAmount::from(amount("183.07 USD")))
+        );
+        assert_eq!(
+            posting("-10 IVV", Some("{183.07 USD}"), Some("197.90 USD")).implied_price(),
+            None
+        );
+        assert_eq!(posting("-400.00 USD", None, None).implied_price(), None);
+    }
+
+    #[test]
+    fn test_from_amount() {
+        let posting = Posting::from_amount(
+            Account::from("Assets:MyBank:Checking"),
+            amount("-400.00 USD"),
+        );
+        assert_eq!(posting.account, Account::from("Assets:MyBank:Checking"));
+        assert_eq!(
+            posting.units.num,
+            Some(Decimal::from_str("-400.00").unwrap())
+        );
+        assert_eq!(posting.units.currency, Some("USD".to_string()));
+        assert_eq!(posting.cost, None);
+        assert_eq!(posting.price, None);
     }
 }
@@ -1,3 +1,5 @@
+use core::marker::PhantomData;
+
 use typed_builder::TypedBuilder;
 
 use crate::amount::Amount;
@@ -36,7 +38,7 @@ use crate::types::date::Date;
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.f78ym1dxtemh>
 #[derive(Clone, Debug, PartialEq, TypedBuilder)]
-pub struct Price {
+pub struct Price<'a> {
     /// Date of the price specification.
     pub date: Date,
 
@@ -45,10 +47,15 @@ pub struct Price {
 
     /// Value the currency is being quoted at.
     pub amount: Amount,
+
+    /// Ties the directive to the lifetime of [Directive](super::Directive). It holds no borrowed
+    /// data, as all its fields are owned.
+    #[builder(default, setter(skip))]
+    phantom: PhantomData<&'a ()>,
 }
 
-impl std::fmt::Display for Price {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Price<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{} price {} {}", self.date, self.currency, self.amount)
     }
 }
@@ -57,7 +64,7 @@ impl std::fmt::Display for Price {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use core::str::FromStr;
 
     use rust_decimal::Decimal;
 
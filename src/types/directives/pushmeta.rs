@@ -0,0 +1,81 @@
+use alloc::string::String;
+
+use typed_builder::TypedBuilder;
+
+use crate::metadata::MetaValue;
+
+/// # Pushmeta Directive
+///
+/// The Pushmeta directive adds a metadata field to every transaction that follows it, until a
+/// matching [PopMeta] removes it.
+///
+/// ## Syntax
+/// ```ignore
+/// pushmeta Key: Value
+/// ...
+/// popmeta Key:
+/// ```ignore
+///
+/// ## Key Points
+/// 1. Works like `pushtag`, but for metadata.
+/// 2. The field is also set on the postings of the transactions.
+/// 3. Metadata written on a transaction takes precedence over pushed metadata.
+///
+/// ## Examples
+/// ```ignore
+/// pushmeta location: "Paris, France"
+///
+/// 2014-07-09 * "Cafe Mogador" "Lamb tagine"
+///   Liabilities:CreditCard   -37.45 USD
+///   Expenses:Restaurant
+///
+/// popmeta location:
+/// ```ignore
+///
+/// Pushed metadata is applied with `Ledger::apply_pushed_meta`.
+#[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
+pub struct PushMeta {
+    /// Key of the pushed metadata field.
+    pub key: String,
+
+    /// Value of the pushed metadata field.
+    pub value: MetaValue,
+}
+
+impl core::fmt::Display for PushMeta {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "pushmeta {}: {}", self.key, self.value)
+    }
+}
+
+/// # Popmeta Directive
+///
+/// Stops applying the metadata field pushed by the last [PushMeta] with the same key.
+#[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
+pub struct PopMeta {
+    /// Key of the popped metadata field.
+    pub key: String,
+}
+
+impl core::fmt::Display for PopMeta {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "popmeta {}:", self.key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let push = PushMeta::builder()
+            .key("location".to_string())
+            .value(MetaValue::Text("Paris, France".to_string()))
+            .build();
+        assert_eq!(push.to_string(), r#"pushmeta location: "Paris, France""#);
+
+        let pop = PopMeta::builder().key("location".to_string()).build();
+        assert_eq!(pop.to_string(), "popmeta location:");
+    }
+}
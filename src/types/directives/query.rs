@@ -1,6 +1,12 @@
+use alloc::borrow::Cow;
+
 use typed_builder::TypedBuilder;
 
 use crate::types::date::Date;
+use crate::util::escape_string;
+
+use super::DirectiveError;
+
 /// # Query Directive
 ///
 /// The Query directive allows embedding SQL queries directly in Beancount files.
@@ -29,23 +35,45 @@ use crate::types::date::Date;
 ///
 /// ## Current Status
 /// - Early development / experimental feature.
-
+///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.nw8fgvy4ub1w>
 #[derive(Clone, Debug, Eq, PartialEq, TypedBuilder)]
-pub struct Query {
+pub struct Query<'a> {
     /// Date on which the query should be run.
     pub date: Date,
 
     /// Name of the query.
-    pub name: String,
+    #[builder(setter(into))]
+    pub name: Cow<'a, str>,
 
     /// Query contents.
-    pub query_string: String,
+    #[builder(setter(into))]
+    pub query_string: Cow<'a, str>,
+}
+
+impl Query<'_> {
+    /// Checks that the query has a name and a query string, neither of which beancount allows
+    /// to be empty or blank.
+    pub fn validate(&self) -> Result<(), DirectiveError> {
+        if self.name.trim().is_empty() {
+            return Err(DirectiveError::Blank("query name"));
+        }
+        if self.query_string.trim().is_empty() {
+            return Err(DirectiveError::Blank("query string"));
+        }
+        Ok(())
+    }
 }
 
-impl std::fmt::Display for Query {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{} query \"{}\" \"{}\"", self.date, self.name, self.query_string)
+impl core::fmt::Display for Query<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{} query \"{}\" \"{}\"",
+            self.date,
+            escape_string(&self.name),
+            escape_string(&self.query_string)
+        )
     }
 }
 
@@ -58,4 +86,41 @@ mod tests {
         let query = Query::builder().date(Date::from_str_unchecked("2014-07-09")).name("france-balances".to_string()).query_string("SELECT account, sum(position) WHERE 'trip-france-2014' in tags".to_string()).build();
         assert_eq!(query.to_string(), r#"2014-07-09 query "france-balances" "SELECT account, sum(position) WHERE 'trip-france-2014' in tags""#);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_display_multiline() {
+        let query = Query::builder()
+            .date(Date::from_str_unchecked("2014-07-09"))
+            .name("france-balances")
+            .query_string("SELECT account, sum(position)\n  WHERE payee = \"SNCF\"")
+            .build();
+        assert_eq!(
+            query.to_string(),
+            "2014-07-09 query \"france-balances\" \"SELECT account, sum(position)\n  \
+             WHERE payee = \\\"SNCF\\\"\""
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let query = |name: &str, query_string: &str| {
+            Query::builder()
+                .date(Date::from_str_unchecked("2014-07-09"))
+                .name(name.to_string())
+                .query_string(query_string.to_string())
+                .build()
+        };
+        assert_eq!(
+            query("france-balances", "SELECT account").validate(),
+            Ok(())
+        );
+        assert_eq!(
+            query("france-balances", "  ").validate(),
+            Err(DirectiveError::Blank("query string"))
+        );
+        assert_eq!(
+            query("", "SELECT account").validate(),
+            Err(DirectiveError::Blank("query name"))
+        );
+    }
+}
@@ -1,12 +1,23 @@
-use std::collections::HashSet;
-use std::fmt;
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{error, fmt};
 
+use rust_decimal::Decimal;
 use typed_builder::TypedBuilder;
 
+use crate::account::Account;
+use crate::amount::Amount;
+use crate::currency::Currency;
 use crate::flags::Flag;
-use crate::metadata::{Link, Tag};
-use crate::types::date::Date;
+use crate::metadata::{Link, Links, Meta, Tag, Tags};
+use crate::types::date::{Date, DateError};
+use crate::util::{escape_string, unquote_string};
 
+use super::balance::BalanceError;
 use super::posting::Posting;
 
 /// # Transaction Directive
@@ -48,7 +59,7 @@ use super::posting::Posting;
 /// ```ignore
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.up4dj751q84w>
 #[derive(Clone, Debug, PartialEq, TypedBuilder)]
-pub struct Transaction {
+pub struct Transaction<'a> {
     pub date: Date,
 
     /// Whether or not a transaction is considered complete.
@@ -60,7 +71,7 @@ pub struct Transaction {
 
     /// Payee of this transaction.
     #[builder(default)]
-    pub payee: Option<String>,
+    pub payee: Option<Cow<'a, str>>,
 
     /// # Payee & Narration
     ///
@@ -106,62 +117,996 @@ pub struct Transaction {
     /// Beancount's Transaction object has separate payee and narration fields.
     ///
     /// For detailed discussion on using payees, refer to "Payees, Subaccounts, and Assets".
-    pub narration: String,
+    ///
+    /// The narration borrows from the parsed input when possible.
+    #[builder(setter(into))]
+    pub narration: Cow<'a, str>,
 
     /// Tags associated with the transaction.
     #[builder(default)]
-    pub tags: HashSet<Tag>,
+    pub tags: Tags,
 
     /// Links associated with the transactions.
     #[builder(default)]
-    pub links: HashSet<Link>,
+    pub links: Links,
 
     /// Postings belonging to this transaction.
     #[builder(default)]
     pub postings: Vec<Posting>,
+
+    /// Metadata attached to the transaction.
+    #[builder(default)]
+    pub meta: Meta,
 }
 
-impl fmt::Display for Transaction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut res = format!("{}", self.date);
-        res.push_str(&format!(" {}", self.flag));
-        if let Some(payee) = &self.payee {
-            res.push_str(&format!(" {}", payee));
-        }
-        // add "" for narration
-
-        res.push_str(&format!(" \"{}\"", self.narration));
-
-        // add tags
-        if !self.tags.is_empty() {
-            res.push_str(&format!(
-                " {}",
-                self.tags
-                    .iter()
-                    .map(|t| t.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            ));
-        }
-
-        // add links
-        if !self.links.is_empty() {
-            res.push_str(&format!(
-                " {}",
-                self.links
-                    .iter()
-                    .map(|l| l.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            ));
-        }
-        // new line
-        res.push('\n');
+impl<'a> Transaction<'a> {
+    /// Creates a two-posting transaction moving `amount` out of `from` and into `to`, e.g. a
+    /// "Transfer to pay credit card".
+    pub fn simple(
+        date: Date,
+        flag: Flag,
+        narration: impl Into<Cow<'a, str>>,
+        from: Account,
+        to: Account,
+        amount: Amount,
+    ) -> Transaction<'a> {
+        let withdrawal = Amount {
+            num: -amount.num,
+            currency: amount.currency.clone(),
+        };
+        Transaction::builder()
+            .date(date)
+            .flag(flag)
+            .narration(narration)
+            .postings(vec![
+                Posting::builder()
+                    .account(from)
+                    .units(withdrawal.into())
+                    .build(),
+                Posting::builder().account(to).units(amount.into()).build(),
+            ])
+            .build()
+    }
+
+    /// Creates a transaction from `postings`, filling the posting without a number, if any, with
+    /// the residual of the others. As in beancount, a residual in several currencies gives one
+    /// posting per currency, and the posting is dropped when there is no residual. Fails if
+    /// several postings have no number, or if the transaction does not balance without one.
+    pub fn try_balanced(
+        date: Date,
+        flag: Flag,
+        narration: impl Into<Cow<'a, str>>,
+        postings: impl IntoIterator<Item = Posting>,
+    ) -> Result<Transaction<'a>, BalanceError> {
+        let mut transaction = Transaction::builder()
+            .date(date)
+            .flag(flag)
+            .narration(narration)
+            .postings(postings.into_iter().collect())
+            .build();
+        let mut elided = transaction
+            .postings
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.units.num.is_none())
+            .map(|(i, _)| i);
+        let elided = match (elided.next(), elided.next()) {
+            (_, Some(_)) => return Err(BalanceError::MultipleElidedPostings),
+            (elided, None) => elided,
+        };
+        let mut residuals = transaction
+            .currency_sums()
+            .into_iter()
+            .filter(|(_, num)| !num.is_zero())
+            .map(|(currency, num)| Amount { num, currency });
+        match elided {
+            Some(i) => {
+                let template = transaction.postings.remove(i);
+                let filled = residuals.map(|residual| Posting {
+                    units: Amount {
+                        num: -residual.num,
+                        currency: residual.currency,
+                    }
+                    .into(),
+                    ..template.clone()
+                });
+                transaction
+                    .postings
+                    .splice(i..i, filled.collect::<Vec<_>>());
+            }
+            None => {
+                if let Some(residual) = residuals.next() {
+                    return Err(BalanceError::Unbalanced(residual));
+                }
+            }
+        }
+        Ok(transaction)
+    }
+
+    /// Sets the narration from its raw form in a beancount file, stripping the surrounding
+    /// quotes and whitespace and unescaping inner quotes, e.g. `"The \"Ritz\""` becomes
+    /// `The "Ritz"`.
+    pub fn set_narration(&mut self, raw: &str) {
+        self.narration = unquote_string(raw).into();
+    }
+
+    /// Sets the payee from its raw form in a beancount file, as `set_narration` does.
+    pub fn set_payee(&mut self, raw: &str) {
+        self.payee = Some(unquote_string(raw).into());
+    }
+
+    /// Adds a tag to the transaction. A leading `#` is stripped, so `#trip` and `trip` add the
+    /// same tag.
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag = tag.strip_prefix('#').unwrap_or(tag);
+        self.tags.insert(tag.to_string());
+    }
+
+    /// Adds a link to the transaction. A leading `^` is stripped, so `^invoice` and `invoice`
+    /// add the same link.
+    pub fn add_link(&mut self, link: &str) {
+        let link = link.strip_prefix('^').unwrap_or(link);
+        self.links.insert(link.to_string());
+    }
+
+    /// Builder-style variant of `add_tag`.
+    pub fn with_tag(mut self, tag: &str) -> Transaction<'a> {
+        self.add_tag(tag);
+        self
+    }
+
+    /// Builder-style variant of `add_link`.
+    pub fn with_link(mut self, link: &str) -> Transaction<'a> {
+        self.add_link(link);
+        self
+    }
+
+    /// Sets the date of every cost spec that does not have one to the date of the transaction,
+    /// as beancount does when booking lots.
+    pub fn fill_cost_dates(&mut self) {
+        for posting in &mut self.postings {
+            if let Some(cost) = &mut posting.cost {
+                if cost.date.is_none() {
+                    cost.date = Some(self.date.clone());
+                }
+            }
+        }
+    }
+
+    /// Returns the effective flag of each posting, in order: its own flag, or the flag of the
+    /// transaction when it has none.
+    pub fn resolved_posting_flags(&self) -> Vec<Flag> {
+        self.postings
+            .iter()
+            .map(|p| p.flag.clone().unwrap_or_else(|| self.flag.clone()))
+            .collect()
+    }
+
+    /// Returns true if the transaction has no payee but its narration contains a `|`, as in
+    /// `"Cafe Mogador | Lamb tagine"`. Such a single string may be a legacy `payee | narration`
+    /// pair written inside one string, and is kept whole as the narration.
+    pub fn is_payee_narration_ambiguous(&self) -> bool {
+        self.payee.is_none() && self.narration.contains('|')
+    }
+
+    /// Returns the accounts posted to more than once, in order of first appearance. This is
+    /// legal, but often a sign of an importer emitting the same leg twice.
+    pub fn duplicate_accounts(&self) -> Vec<&Account> {
+        let mut seen = BTreeSet::new();
+        let mut duplicates = Vec::new();
+        for posting in &self.postings {
+            if !seen.insert(&posting.account) && !duplicates.contains(&&posting.account) {
+                duplicates.push(&posting.account);
+            }
+        }
+        duplicates
+    }
+
+    /// Sums the weights of the postings per currency. A balanced transaction sums to zero in
+    /// every currency, so a nonzero entry is the residual to look at when it does not balance.
+    /// Postings whose weight is incomplete are ignored.
+    pub fn currency_sums(&self) -> BTreeMap<Currency, Decimal> {
+        let mut sums = BTreeMap::new();
+        for weight in self.postings.iter().filter_map(Posting::weight) {
+            *sums.entry(weight.currency).or_insert(Decimal::ZERO) += weight.num;
+        }
+        sums
+    }
+
+    /// Sums the positive weights of the postings in `currency`, i.e. the gross amount moved by
+    /// the transaction in that currency, e.g. the salary before taxes of a paycheck. Postings
+    /// whose weight is incomplete are ignored.
+    pub fn total_volume(&self, currency: &Currency) -> Decimal {
+        self.postings
+            .iter()
+            .filter_map(Posting::weight)
+            .filter(|w| &w.currency == currency && w.num.is_sign_positive())
+            .map(|w| w.num)
+            .sum()
+    }
+}
 
+impl<'a> Transaction<'a> {
+    /// Renders the transaction like `Display`, but with the posting numbers right-aligned so that
+    /// they end at `amount_column`, as `bean-format` does. Accounts too long for the column are
+    /// followed by two spaces instead.
+    pub fn to_formatted_string(&self, amount_column: usize) -> String {
+        let mut res = String::new();
+        self.write_header(&mut res, false)
+            .expect("writing to a String cannot fail");
         for posting in &self.postings {
-            res.push_str(&format!("  {}", posting));
+            let mut line = String::from("  ");
+            if let Some(flag) = &posting.flag {
+                line.push_str(&format!("{} ", flag));
+            }
+            line.push_str(&posting.account.to_string());
+            if posting.units.num.is_some() || posting.units.currency.is_some() {
+                // Without a number, the currency still goes where it would follow one.
+                let num = posting.units.num.map(|n| n.to_string()).unwrap_or_default();
+                let width = line.chars().count() + num.chars().count();
+                line.push_str(&" ".repeat(amount_column.saturating_sub(width).max(2)));
+                line.push_str(&num);
+                if let Some(currency) = &posting.units.currency {
+                    line.push_str(&format!(" {}", currency));
+                }
+            }
+            if let Some(cost) = &posting.cost {
+                line.push_str(&format!(" {}", cost));
+            }
+            if let Some(price) = &posting.price {
+                line.push_str(&format!(" @ {}", price));
+            }
+            res.push_str(&line);
             res.push('\n');
         }
-        write!(f, "{}", res)
+        res
+    }
+
+    /// Renders the transaction like `Display`, but with the `txn` keyword in place of the `*`
+    /// flag. Transactions with any other flag keep it, since `txn` always means `*`.
+    pub fn to_string_with_keyword(&self) -> String {
+        let mut res = String::new();
+        self.write_header(&mut res, true)
+            .expect("writing to a String cannot fail");
+        for posting in &self.postings {
+            res.push_str(&format!("  {}\n", posting));
+        }
+        res
+    }
+
+    /// Writes the first line of the transaction: date, flag, payee, narration, tags and links.
+    /// With `use_txn_keyword`, a `*` flag is written as `txn`.
+    fn write_header(&self, f: &mut impl fmt::Write, use_txn_keyword: bool) -> fmt::Result {
+        if use_txn_keyword && self.flag == Flag::Okay {
+            write!(f, "{} txn", self.date)?;
+        } else {
+            write!(f, "{} {}", self.date, self.flag)?;
+        }
+        if let Some(payee) = &self.payee {
+            write!(f, " \"{}\"", escape_string(payee))?;
+        }
+        write!(f, " \"{}\"", escape_string(&self.narration))?;
+        let mut tags: Vec<&Tag> = self.tags.iter().collect();
+        tags.sort();
+        for tag in tags {
+            write!(f, " #{}", tag)?;
+        }
+        let mut links: Vec<&Link> = self.links.iter().collect();
+        links.sort();
+        for link in links {
+            write!(f, " ^{}", link)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for Transaction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_header(f, false)?;
+        for posting in &self.postings {
+            writeln!(f, "  {}", posting)?;
+        }
+        Ok(())
+    }
+}
+
+/// The header fields of a transaction with a digest of its postings, e.g. for a list view that
+/// does not need the postings themselves.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransactionSummary {
+    pub date: Date,
+    pub flag: Flag,
+    pub payee: Option<String>,
+    pub narration: String,
+    /// The number of postings of the transaction.
+    pub posting_count: usize,
+    /// The currencies of the units of the postings.
+    pub currencies: BTreeSet<Currency>,
+}
+
+impl From<&Transaction<'_>> for TransactionSummary {
+    fn from(transaction: &Transaction<'_>) -> Self {
+        TransactionSummary {
+            date: transaction.date.clone(),
+            flag: transaction.flag.clone(),
+            payee: transaction.payee.as_ref().map(|p| p.to_string()),
+            narration: transaction.narration.to_string(),
+            posting_count: transaction.postings.len(),
+            currencies: transaction
+                .postings
+                .iter()
+                .filter_map(|p| p.units.currency.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Parses the first line of a transaction, e.g.
+/// `2014-05-05 * "Cafe Mogador" "Lamb tagine" #tag ^link`, into a transaction without postings.
+///
+/// The flag may be the `txn` keyword. A single string is the narration; with two strings, the
+/// first is the payee. The legacy `|` separator between payee and narration is accepted. Tags and
+/// links must follow the strings.
+pub fn parse_transaction_header(line: &str) -> Result<Transaction<'static>, TxnParseError> {
+    let line = line.trim();
+    let (date, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let date: Date = date.parse().map_err(TxnParseError::Date)?;
+    let rest = rest.trim_start();
+    let (flag, mut rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if flag.is_empty() || (flag != "txn" && flag.chars().count() != 1) || "\"#^".contains(flag) {
+        return Err(TxnParseError::Flag(flag.to_string()));
+    }
+
+    let mut strings = Vec::new();
+    let mut tags = Vec::new();
+    let mut links = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if rest.starts_with('"') {
+            if !tags.is_empty() || !links.is_empty() {
+                return Err(TxnParseError::UnexpectedToken(rest.to_string()));
+            }
+            let end = closing_quote(rest)
+                .ok_or_else(|| TxnParseError::UnterminatedString(rest.to_string()))?;
+            strings.push(unquote_string(&rest[..=end]));
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let (token, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        match token.chars().next() {
+            Some('#') if token.len() > 1 => tags.push(token),
+            Some('^') if token.len() > 1 => links.push(token),
+            Some('|') if token.len() == 1 && strings.len() == 1 => {}
+            _ => return Err(TxnParseError::UnexpectedToken(token.to_string())),
+        }
+        rest = tail;
+    }
+
+    let (payee, narration) = match strings.len() {
+        0 => (None, String::new()),
+        1 => (None, strings.remove(0)),
+        2 => {
+            let narration = strings.remove(1);
+            (Some(strings.remove(0)), narration)
+        }
+        _ => return Err(TxnParseError::TooManyStrings(line.to_string())),
+    };
+    let mut transaction = Transaction::builder()
+        .date(date)
+        .flag(Flag::from(flag))
+        .payee(payee.map(Cow::from))
+        .narration(narration)
+        .build();
+    for tag in tags {
+        transaction.add_tag(tag);
+    }
+    for link in links {
+        transaction.add_link(link);
+    }
+    Ok(transaction)
+}
+
+/// Returns the byte index of the quote closing the string that `s` starts with, skipping
+/// escaped characters.
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Errors raised by [parse_transaction_header].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TxnParseError {
+    /// The line does not start with a valid date.
+    Date(DateError),
+    /// The date is not followed by a flag or the `txn` keyword.
+    Flag(String),
+    /// A string is not closed by a double quote.
+    UnterminatedString(String),
+    /// There are more than two strings, i.e. more than a payee and a narration.
+    TooManyStrings(String),
+    /// A token is neither a string, a tag nor a link, or a string follows a tag or link.
+    UnexpectedToken(String),
+}
+
+impl fmt::Display for TxnParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TxnParseError::Date(e) => write!(f, "invalid transaction date: {}", e),
+            TxnParseError::Flag(s) => write!(f, "invalid transaction flag: {}", s),
+            TxnParseError::UnterminatedString(s) => write!(f, "unterminated string: {}", s),
+            TxnParseError::TooManyStrings(s) => {
+                write!(f, "too many strings in transaction header: {}", s)
+            }
+            TxnParseError::UnexpectedToken(s) => write!(f, "unexpected token: {}", s),
+        }
+    }
+}
+
+impl error::Error for TxnParseError {}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use crate::{amount::IncompleteAmount, directives::position::CostSpec};
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let units = |num: &str, currency: &str| {
+            IncompleteAmount::builder()
+                .num(Some(Decimal::from_str(num).unwrap()))
+                .currency(Some(Currency::from(currency)))
+                .build()
+        };
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-07-11"))
+            .payee(Some("ETrade".into()))
+            .narration("Sold shares of S&P 500".to_string())
+            .tags(Tags::from(["trading".to_string()]))
+            .links(Links::from(["ivv-2014".to_string()]))
+            .postings(vec![
+                Posting::builder()
+                    .account(Account::from("Assets:ETrade:IVV"))
+                    .units(units("-10", "IVV"))
+                    .cost(Some(
+                        CostSpec::builder()
+                            .number_per(Some(Decimal::from_str("183.07").unwrap()))
+                            .currency(Some(Currency::from("USD")))
+                            .build(),
+                    ))
+                    .price(Some(units("197.90", "USD")))
+                    .build(),
+                Posting::builder()
+                    .account(Account::from("Assets:ETrade:Cash"))
+                    .units(units("1979.90", "USD"))
+                    .build(),
+                Posting::builder()
+                    .account(Account::from("Income:ETrade:CapitalGains"))
+                    .units(IncompleteAmount::builder().build())
+                    .build(),
+            ])
+            .build();
+        assert_eq!(
+            transaction.to_string(),
+            "2014-07-11 * \"ETrade\" \"Sold shares of S&P 500\" #trading ^ivv-2014\n\
+             \x20 Assets:ETrade:IVV\t-10 IVV\t{183.07 USD}\t@ 197.90 USD\n\
+             \x20 Assets:ETrade:Cash\t1979.90 USD\n\
+             \x20 Income:ETrade:CapitalGains\t0\n"
+        );
+    }
+
+    #[test]
+    fn test_display_narration_only() {
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-05"))
+            .narration("Lamb tagine with wine")
+            .build();
+        let header = transaction.to_string();
+        assert_eq!(header, "2014-05-05 * \"Lamb tagine with wine\"\n");
+        assert_eq!(header.matches('"').count(), 2);
+
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-05"))
+            .narration("")
+            .build();
+        assert_eq!(transaction.to_string(), "2014-05-05 * \"\"\n");
+    }
+
+    #[test]
+    fn test_is_payee_narration_ambiguous() {
+        let mut transaction =
+            parse_transaction_header(r#"2014-05-05 * "Cafe Mogador | Lamb tagine""#).unwrap();
+        assert!(transaction.is_payee_narration_ambiguous());
+
+        transaction.set_payee("Cafe Mogador");
+        assert!(!transaction.is_payee_narration_ambiguous());
+
+        let transaction =
+            parse_transaction_header(r#"2014-05-05 * "Cafe Mogador" | "Lamb tagine""#).unwrap();
+        assert!(!transaction.is_payee_narration_ambiguous());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut transaction = Transaction::simple(
+            Date::from_str_unchecked("2014-07-11"),
+            Flag::Okay,
+            "Sold shares of S&P 500".to_string(),
+            Account::from("Assets:ETrade:IVV"),
+            Account::from("Assets:ETrade:Cash"),
+            Amount::from((Decimal::from_str("1979.90").unwrap(), "USD")),
+        );
+        transaction.payee = Some("ETrade".into());
+        transaction.postings[0].units = IncompleteAmount::builder()
+            .num(Some(Decimal::from(-10)))
+            .currency(Some(Currency::from("IVV")))
+            .build();
+        transaction.postings.push(
+            Posting::builder()
+                .account(Account::from("Income:ETrade:CapitalGains"))
+                .units(IncompleteAmount::builder().build())
+                .build(),
+        );
+
+        assert_eq!(
+            TransactionSummary::from(&transaction),
+            TransactionSummary {
+                date: Date::from_str_unchecked("2014-07-11"),
+                flag: Flag::Okay,
+                payee: Some("ETrade".to_string()),
+                narration: "Sold shares of S&P 500".to_string(),
+                posting_count: 3,
+                currencies: BTreeSet::from([Currency::from("IVV"), Currency::from("USD")]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_display_escapes_strings() {
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-05"))
+            .payee(Some(r#"Cafe "Mogador""#.into()))
+            .narration(r"Lamb tagine \ wine".to_string())
+            .build();
+        assert_eq!(
+            transaction.to_string(),
+            "2014-05-05 * \"Cafe \\\"Mogador\\\"\" \"Lamb tagine \\\\ wine\"\n"
+        );
+    }
+
+    #[test]
+    fn test_fill_cost_dates() {
+        let cost = |date: Option<&str>| {
+            CostSpec::builder()
+                .number_per(Some(Decimal::from_str("183.07").unwrap()))
+                .currency(Some(Currency::from("USD")))
+                .date(date.map(Date::from_str_unchecked))
+                .build()
+        };
+        let posting = |cost: CostSpec| {
+            Posting::builder()
+                .account(Account::from("Assets:ETrade:IVV"))
+                .units(
+                    IncompleteAmount::builder()
+                        .num(Some(Decimal::from(10)))
+                        .currency(Some(Currency::from("IVV")))
+                        .build(),
+                )
+                .cost(Some(cost))
+                .build()
+        };
+        let mut transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-02-11"))
+            .narration("Bought shares of S&P 500".to_string())
+            .postings(vec![
+                posting(cost(None)),
+                posting(cost(Some("2014-01-01"))),
+                Posting::builder()
+                    .account(Account::from("Assets:ETrade:Cash"))
+                    .units(IncompleteAmount::builder().build())
+                    .build(),
+            ])
+            .build();
+        transaction.fill_cost_dates();
+
+        let dates: Vec<Option<Date>> = transaction
+            .postings
+            .iter()
+            .map(|p| p.cost.as_ref().and_then(|c| c.date.clone()))
+            .collect();
+        assert_eq!(
+            dates,
+            vec![
+                Some(Date::from_str_unchecked("2014-02-11")),
+                Some(Date::from_str_unchecked("2014-01-01")),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simple() {
+        let transaction = Transaction::simple(
+            Date::from_str_unchecked("2012-11-03"),
+            Flag::Okay,
+            "Transfer to pay credit card".to_string(),
+            Account::from("Assets:MyBank:Checking"),
+            Account::from("Liabilities:CreditCard"),
+            Amount::builder()
+                .num(Decimal::from_str("400.00").unwrap())
+                .currency(Currency::from("USD"))
+                .build(),
+        );
+
+        assert_eq!(transaction.postings.len(), 2);
+        let from = &transaction.postings[0];
+        let to = &transaction.postings[1];
+        assert_eq!(from.account, Account::from("Assets:MyBank:Checking"));
+        assert_eq!(to.account, Account::from("Liabilities:CreditCard"));
+        assert_eq!(from.units.num, Some(Decimal::from_str("-400.00").unwrap()));
+        assert_eq!(from.units.currency, to.units.currency);
+        assert_eq!(
+            from.units.num.unwrap() + to.units.num.unwrap(),
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn test_to_formatted_string() {
+        let posting = |account: &str, num: Option<&str>| {
+            Posting::builder()
+                .account(Account::from(account))
+                .units(
+                    IncompleteAmount::builder()
+                        .num(num.map(|n| Decimal::from_str(n).unwrap()))
+                        .currency(num.map(|_| Currency::from("USD")))
+                        .build(),
+                )
+                .build()
+        };
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-03-19"))
+            .payee(Some("Acme Corp".into()))
+            .narration("Bi-monthly salary payment".to_string())
+            .postings(vec![
+                posting("Assets:MyBank:Checking", Some("3062.68")),
+                posting("Income:AcmeCorp:Salary", Some("-4615.38")),
+                posting("Expenses:Taxes:TY2014:Federal:Withholding", Some("920.53")),
+                posting("Expenses:Taxes:TY2014:SocSec", None),
+            ])
+            .build();
+
+        assert_eq!(
+            transaction.to_formatted_string(40),
+            r#"2014-03-19 * "Acme Corp" "Bi-monthly salary payment"
+  Assets:MyBank:Checking         3062.68 USD
+  Income:AcmeCorp:Salary        -4615.38 USD
+  Expenses:Taxes:TY2014:Federal:Withholding  920.53 USD
+  Expenses:Taxes:TY2014:SocSec
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_formatted_string_currency_without_number() {
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-05"))
+            .narration("Lunch")
+            .postings(vec![
+                Posting::from_amount(
+                    Account::from("Liabilities:CreditCard"),
+                    "-37.45 USD".parse().unwrap(),
+                ),
+                Posting::builder()
+                    .account(Account::from("Expenses:Restaurant"))
+                    .units(
+                        IncompleteAmount::builder()
+                            .currency(Some(Currency::from("USD")))
+                            .build(),
+                    )
+                    .build(),
+            ])
+            .build();
+
+        assert_eq!(
+            transaction.to_formatted_string(32),
+            r#"2014-05-05 * "Lunch"
+  Liabilities:CreditCard  -37.45 USD
+  Expenses:Restaurant            USD
+"#
+        );
+    }
+
+    #[test]
+    fn test_add_tags_and_links() {
+        let mut transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-04-23"))
+            .narration("Flight to Berlin".to_string())
+            .build()
+            .with_tag("#berlin-trip-2014")
+            .with_link("^invoice-pepe-studios-jan14");
+        transaction.add_tag("travel");
+        transaction.add_tag("berlin-trip-2014");
+
+        assert_eq!(transaction.tags.len(), 2);
+        assert!(transaction.tags.contains("berlin-trip-2014"));
+        assert!(transaction.tags.contains("travel"));
+        assert!(transaction.links.contains("invoice-pepe-studios-jan14"));
+        assert_eq!(
+            transaction.to_string(),
+            "2014-04-23 * \"Flight to Berlin\" #berlin-trip-2014 #travel ^invoice-pepe-studios-jan14\n"
+        );
+    }
+
+    #[test]
+    fn test_to_string_with_keyword() {
+        let mut transaction = Transaction::simple(
+            Date::from_str_unchecked("2014-05-05"),
+            Flag::Okay,
+            "Cafe Mogador".to_string(),
+            Account::from("Liabilities:CreditCard:CapitalOne"),
+            Account::from("Expenses:Food:Restaurant"),
+            Amount::from((Decimal::new(3745, 2), "USD")),
+        );
+        assert_eq!(
+            transaction.to_string(),
+            "2014-05-05 * \"Cafe Mogador\"\n  Liabilities:CreditCard:CapitalOne\t-37.45 USD\n  Expenses:Food:Restaurant\t37.45 USD\n"
+        );
+        assert_eq!(
+            transaction.to_string_with_keyword(),
+            "2014-05-05 txn \"Cafe Mogador\"\n  Liabilities:CreditCard:CapitalOne\t-37.45 USD\n  Expenses:Food:Restaurant\t37.45 USD\n"
+        );
+
+        transaction.flag = Flag::Warning;
+        assert_eq!(
+            transaction.to_string_with_keyword(),
+            transaction.to_string()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_accounts() {
+        let mut transaction = Transaction::simple(
+            Date::from_str_unchecked("2014-05-05"),
+            Flag::Okay,
+            "Cash withdrawal".to_string(),
+            Account::from("Assets:US:BofA:Checking"),
+            Account::from("Assets:Cash"),
+            Amount::from((Decimal::from(100), "USD")),
+        );
+        assert!(transaction.duplicate_accounts().is_empty());
+
+        transaction.postings.push(transaction.postings[1].clone());
+        transaction.postings.push(transaction.postings[1].clone());
+        assert_eq!(
+            transaction.duplicate_accounts(),
+            vec![&Account::from("Assets:Cash")]
+        );
+    }
+
+    #[test]
+    fn test_currency_sums() {
+        let mut transaction = Transaction::simple(
+            Date::from_str_unchecked("2014-05-05"),
+            Flag::Okay,
+            "Cash withdrawal".to_string(),
+            Account::from("Assets:US:BofA:Checking"),
+            Account::from("Assets:Cash"),
+            Amount::from((Decimal::from(100), "USD")),
+        );
+        assert_eq!(
+            transaction.currency_sums(),
+            BTreeMap::from([("USD".to_string(), Decimal::ZERO)])
+        );
+
+        transaction.postings[1].units.num = Some(Decimal::from_str("99.50").unwrap());
+        transaction.postings.push(
+            Posting::builder()
+                .account(Account::from("Expenses:Fees"))
+                .units(IncompleteAmount::builder().build())
+                .build(),
+        );
+        assert_eq!(
+            transaction.currency_sums(),
+            BTreeMap::from([("USD".to_string(), Decimal::from_str("-0.50").unwrap())])
+        );
+    }
+
+    #[test]
+    fn test_try_balanced() {
+        let posting = |account: &str, amount: Option<&str>| {
+            Posting::builder()
+                .account(Account::from(account))
+                .units(amount.map_or_else(
+                    || IncompleteAmount::builder().build(),
+                    |a| a.parse::<Amount>().unwrap().into(),
+                ))
+                .build()
+        };
+        let try_balanced = |postings| {
+            Transaction::try_balanced(
+                Date::from_str_unchecked("2014-05-05"),
+                Flag::Okay,
+                "Lunch",
+                postings,
+            )
+        };
+
+        let transaction = try_balanced(vec![
+            posting("Liabilities:CreditCard", Some("-37.45 USD")),
+            posting("Expenses:Restaurant", Some("30.00 USD")),
+            posting("Expenses:Tips", None),
+        ])
+        .unwrap();
+        assert_eq!(transaction.postings.len(), 3);
+        assert_eq!(
+            transaction.postings[2].account,
+            Account::from("Expenses:Tips")
+        );
+        assert_eq!(
+            transaction.postings[2].units,
+            IncompleteAmount::from("7.45 USD".parse::<Amount>().unwrap())
+        );
+
+        assert_eq!(
+            try_balanced(vec![
+                posting("Liabilities:CreditCard", Some("-37.45 USD")),
+                posting("Expenses:Restaurant", Some("30.00 USD")),
+            ]),
+            Err(BalanceError::Unbalanced("-7.45 USD".parse().unwrap()))
+        );
+        assert_eq!(
+            try_balanced(vec![
+                posting("Liabilities:CreditCard", Some("-37.45 USD")),
+                posting("Expenses:Restaurant", None),
+                posting("Expenses:Tips", None),
+            ]),
+            Err(BalanceError::MultipleElidedPostings)
+        );
+    }
+
+    #[test]
+    fn test_total_volume() {
+        let posting = |account: &str, amount: &str| {
+            Posting::from_amount(Account::from(account), amount.parse().unwrap())
+        };
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-15"))
+            .narration("Payroll")
+            .postings(vec![
+                posting("Income:US:Hooli:Salary", "-4615.38 USD"),
+                posting("Expenses:Taxes:Federal", "1180.00 USD"),
+                posting("Expenses:Health:Dental", "2.90 USD"),
+                posting("Assets:US:BofA:Checking", "3432.48 USD"),
+                posting("Assets:US:Hooli:Vacation", "5 VACHR"),
+            ])
+            .build();
+
+        assert_eq!(
+            transaction.total_volume(&"USD".to_string()),
+            Decimal::from_str("4615.38").unwrap()
+        );
+        assert_eq!(
+            transaction.total_volume(&"VACHR".to_string()),
+            Decimal::from(5)
+        );
+        assert_eq!(transaction.total_volume(&"EUR".to_string()), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_set_narration_and_payee() {
+        let mut transaction = Transaction::builder()
+            .date(Date::from_str_unchecked("2014-05-05"))
+            .narration(String::new())
+            .build();
+        transaction.set_payee(" \"Cafe \\\"Mogador\\\"\" ");
+        transaction.set_narration("\"Lamb tagine\"");
+
+        assert_eq!(transaction.payee, Some("Cafe \"Mogador\"".into()));
+        assert_eq!(transaction.narration, "Lamb tagine");
+        assert_eq!(
+            transaction.to_string(),
+            "2014-05-05 * \"Cafe \\\"Mogador\\\"\" \"Lamb tagine\"\n"
+        );
+    }
+
+    #[test]
+    fn test_resolved_posting_flags() {
+        let mut transaction = Transaction::simple(
+            Date::from_str_unchecked("2014-05-05"),
+            Flag::Okay,
+            "Cafe Mogador".to_string(),
+            Account::from("Liabilities:CreditCard:CapitalOne"),
+            Account::from("Expenses:Food:Restaurant"),
+            Amount::from((Decimal::new(3745, 2), "USD")),
+        );
+        transaction.postings[1].flag = Some(Flag::Warning);
+
+        assert_eq!(
+            transaction.resolved_posting_flags(),
+            vec![Flag::Okay, Flag::Warning]
+        );
+    }
+
+    #[test]
+    fn test_parse_transaction_header() {
+        let transaction =
+            parse_transaction_header(r#"2014-05-05 * "Cafe Mogador" "Lamb tagine with wine""#)
+                .unwrap();
+        assert_eq!(transaction.date, Date::from_str_unchecked("2014-05-05"));
+        assert_eq!(transaction.flag, Flag::Okay);
+        assert_eq!(transaction.payee, Some("Cafe Mogador".into()));
+        assert_eq!(transaction.narration, "Lamb tagine with wine");
+        assert!(transaction.postings.is_empty());
+
+        let transaction =
+            parse_transaction_header(r#"2014-05-05 txn "Lamb \"tagine\" with wine""#).unwrap();
+        assert_eq!(transaction.flag, Flag::Okay);
+        assert_eq!(transaction.payee, None);
+        assert_eq!(transaction.narration, r#"Lamb "tagine" with wine"#);
+
+        let transaction = parse_transaction_header(
+            r#"2014-04-23 ! "Flight to Berlin" #berlin-trip-2014 ^invoice-jan14 #travel"#,
+        )
+        .unwrap();
+        assert_eq!(transaction.flag, Flag::Warning);
+        assert_eq!(transaction.narration, "Flight to Berlin");
+        assert_eq!(transaction.tags.len(), 2);
+        assert!(transaction.tags.contains("berlin-trip-2014"));
+        assert!(transaction.tags.contains("travel"));
+        assert!(transaction.links.contains("invoice-jan14"));
+
+        let transaction = parse_transaction_header("2014-05-05 *").unwrap();
+        assert_eq!(transaction.narration, "");
+    }
+
+    #[test]
+    fn test_parse_transaction_header_errors() {
+        assert!(matches!(
+            parse_transaction_header(r#"2014-05-32 * "Lunch""#),
+            Err(TxnParseError::Date(_))
+        ));
+        assert_eq!(
+            parse_transaction_header(r#"2014-05-05 "Lunch""#),
+            Err(TxnParseError::Flag(r#""Lunch""#.to_string()))
+        );
+        assert_eq!(
+            parse_transaction_header(r#"2014-05-05 * "Lunch"#),
+            Err(TxnParseError::UnterminatedString(r#""Lunch"#.to_string()))
+        );
+        assert!(matches!(
+            parse_transaction_header(r#"2014-05-05 * "a" "b" "c""#),
+            Err(TxnParseError::TooManyStrings(_))
+        ));
+        assert_eq!(
+            parse_transaction_header(r#"2014-05-05 * #tag "Lunch""#),
+            Err(TxnParseError::UnexpectedToken(r#""Lunch""#.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_borrowed_narration() {
+        let line = String::from("2014-05-05 * \"Lamb tagine with wine\"");
+        let transaction = Transaction::builder()
+            .date(Date::from_str_unchecked(&line[..10]))
+            .narration(&line[14..line.len() - 1])
+            .build();
+
+        assert!(matches!(transaction.narration, Cow::Borrowed(_)));
+        assert_eq!(transaction.narration, "Lamb tagine with wine");
+        assert_eq!(transaction.to_string(), format!("{}\n", line));
     }
 }
@@ -0,0 +1,239 @@
+use core::{error, fmt};
+
+use crate::{
+    account::AccountError,
+    amount::AmountError,
+    currency::{CurrencyError, CurrencyPairError},
+    date::DateError,
+    directives::{
+        balance::BalanceError, open::BookingMethodError, position::CostSpecError,
+        posting::PostingConsistencyError, transaction::TxnParseError, DirectiveError,
+    },
+    inventory::BookingError,
+    CurrencyConstraintError,
+};
+
+/// Any error raised by the crate, for callers that handle them all the same way. Each variant
+/// wraps the error of one module, and converts from it so that `?` works across modules.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BeancountError {
+    Account(AccountError),
+    Amount(AmountError),
+    Currency(CurrencyError),
+    CurrencyPair(CurrencyPairError),
+    Date(DateError),
+    Balance(BalanceError),
+    Booking(BookingError),
+    BookingMethod(BookingMethodError),
+    CostSpec(CostSpecError),
+    Posting(PostingConsistencyError),
+    CurrencyConstraint(CurrencyConstraintError),
+    TransactionHeader(TxnParseError),
+    Directive(DirectiveError),
+}
+
+impl fmt::Display for BeancountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BeancountError::Account(e) => e.fmt(f),
+            BeancountError::Amount(e) => e.fmt(f),
+            BeancountError::Currency(e) => e.fmt(f),
+            BeancountError::CurrencyPair(e) => e.fmt(f),
+            BeancountError::Date(e) => e.fmt(f),
+            BeancountError::Balance(e) => e.fmt(f),
+            BeancountError::Booking(e) => e.fmt(f),
+            BeancountError::BookingMethod(e) => e.fmt(f),
+            BeancountError::CostSpec(e) => e.fmt(f),
+            BeancountError::Posting(e) => e.fmt(f),
+            BeancountError::CurrencyConstraint(e) => e.fmt(f),
+            BeancountError::TransactionHeader(e) => e.fmt(f),
+            BeancountError::Directive(e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for BeancountError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            BeancountError::Account(e) => Some(e),
+            BeancountError::Amount(e) => Some(e),
+            BeancountError::Currency(e) => Some(e),
+            BeancountError::CurrencyPair(e) => Some(e),
+            BeancountError::Date(e) => Some(e),
+            BeancountError::Balance(e) => Some(e),
+            BeancountError::Booking(e) => Some(e),
+            BeancountError::BookingMethod(e) => Some(e),
+            BeancountError::CostSpec(e) => Some(e),
+            BeancountError::Posting(e) => Some(e),
+            BeancountError::CurrencyConstraint(e) => Some(e),
+            BeancountError::TransactionHeader(e) => Some(e),
+            BeancountError::Directive(e) => Some(e),
+        }
+    }
+}
+
+impl From<AccountError> for BeancountError {
+    fn from(e: AccountError) -> Self {
+        BeancountError::Account(e)
+    }
+}
+
+impl From<AmountError> for BeancountError {
+    fn from(e: AmountError) -> Self {
+        BeancountError::Amount(e)
+    }
+}
+
+impl From<CurrencyError> for BeancountError {
+    fn from(e: CurrencyError) -> Self {
+        BeancountError::Currency(e)
+    }
+}
+
+impl From<CurrencyPairError> for BeancountError {
+    fn from(e: CurrencyPairError) -> Self {
+        BeancountError::CurrencyPair(e)
+    }
+}
+
+impl From<DateError> for BeancountError {
+    fn from(e: DateError) -> Self {
+        BeancountError::Date(e)
+    }
+}
+
+impl From<BalanceError> for BeancountError {
+    fn from(e: BalanceError) -> Self {
+        BeancountError::Balance(e)
+    }
+}
+
+impl From<BookingError> for BeancountError {
+    fn from(e: BookingError) -> Self {
+        BeancountError::Booking(e)
+    }
+}
+
+impl From<BookingMethodError> for BeancountError {
+    fn from(e: BookingMethodError) -> Self {
+        BeancountError::BookingMethod(e)
+    }
+}
+
+impl From<CostSpecError> for BeancountError {
+    fn from(e: CostSpecError) -> Self {
+        BeancountError::CostSpec(e)
+    }
+}
+
+impl From<PostingConsistencyError> for BeancountError {
+    fn from(e: PostingConsistencyError) -> Self {
+        BeancountError::Posting(e)
+    }
+}
+
+impl From<CurrencyConstraintError> for BeancountError {
+    fn from(e: CurrencyConstraintError) -> Self {
+        BeancountError::CurrencyConstraint(e)
+    }
+}
+
+impl From<TxnParseError> for BeancountError {
+    fn from(e: TxnParseError) -> Self {
+        BeancountError::TransactionHeader(e)
+    }
+}
+
+impl From<DirectiveError> for BeancountError {
+    fn from(e: DirectiveError) -> Self {
+        BeancountError::Directive(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{convert::TryFrom, str::FromStr};
+
+    use rust_decimal::Decimal;
+
+    use crate::{
+        account::Account,
+        amount::{Amount, IncompleteAmount},
+        currency::{validate_currency, CurrencyPair},
+        date::Date,
+        directives::{
+            balance::Balance, open::Booking, position::CostSpec, posting::Posting,
+            transaction::parse_transaction_header,
+        },
+        inventory::Inventory,
+    };
+
+    use super::*;
+
+    fn message<T: fmt::Debug, E: Into<BeancountError>>(res: Result<T, E>) -> String {
+        res.map_err(Into::into).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(
+            message(Account::from_str("Asset:Cash")),
+            "unknown account type: Asset"
+        );
+        assert_eq!(
+            message(Amount::try_from(IncompleteAmount::builder().build())),
+            "amount has no number"
+        );
+        assert_eq!(
+            message(validate_currency("usd")),
+            "currency must start with a capital letter"
+        );
+        assert_eq!(
+            message(CurrencyPair::from_str("USD")),
+            "invalid currency pair: USD"
+        );
+        assert_eq!(
+            message(Date::from_str("2014-02-30")),
+            "invalid date: 2014-02-30"
+        );
+        assert_eq!(
+            message(Inventory::new().reduce_by_label("-1 IVV".parse().unwrap(), "lot-1")),
+            "no lot matches lot-1"
+        );
+        assert_eq!(
+            message(Balance::try_new(
+                Date::from_str_unchecked("2014-12-26"),
+                Account::from("Liabilities:US:CreditCard"),
+                "-3492.02 USD".parse().unwrap(),
+                Some(Decimal::NEGATIVE_ONE),
+            )),
+            "negative balance tolerance: -1"
+        );
+        assert_eq!(
+            message(Booking::try_from("RANDOM")),
+            "unknown booking method: RANDOM"
+        );
+        assert_eq!(
+            message(CostSpec::from_str("183.07 USD")),
+            "invalid cost specification: 183.07 USD"
+        );
+        let posting = Posting::builder()
+            .account(Account::from("Assets:ETrade:IVV"))
+            .units("-10 IVV".parse::<Amount>().unwrap().into())
+            .cost(Some(CostSpec::from_str("{183.07 USD}").unwrap()))
+            .price(Some("197.90 EUR".parse::<Amount>().unwrap().into()))
+            .build();
+        assert_eq!(
+            message(posting.currency_consistency()),
+            "cost currency USD does not match price currency EUR"
+        );
+        assert_eq!(
+            message(parse_transaction_header(r#"2014-05-05 * "Lunch"#)),
+            r#"unterminated string: "Lunch"#
+        );
+        assert_eq!(
+            message::<(), _>(Err(DirectiveError::Blank("query name"))),
+            "query name must not be blank"
+        );
+    }
+}
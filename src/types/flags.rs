@@ -1,4 +1,5 @@
-use std::fmt;
+use alloc::string::{String, ToString};
+use core::fmt;
 
 /// A flag for a posting or transaction.
 ///
@@ -11,22 +12,17 @@ use std::fmt;
 /// assert_eq!(Flag::from(":)"), Flag::Other(":)".into()));
 /// ```ignore
 // TODO: Make sure that the variant Other("*") can't be created, since Other("*") != Okay
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub enum Flag {
+    #[default]
     Okay,
     Warning,
     Other(String),
 }
 
-impl Default for Flag {
-    fn default() -> Self {
-        Flag::Okay
-    }
-}
-
 impl From<&str> for Flag {
     fn from(s: &str) -> Self {
-        match &*s {
+        match s {
             "*" | "txn" => Flag::Okay,
             "!" => Flag::Warning,
             _ => Flag::Other(s.to_string()),
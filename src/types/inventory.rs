@@ -0,0 +1,360 @@
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{error, fmt};
+
+use rust_decimal::Decimal;
+
+use crate::amount::Amount;
+use crate::date::Date;
+use crate::directives::position::{CostSpec, Position};
+
+/// The positions held in an account: amounts of commodities, some of them held at cost.
+///
+/// Positions with the same commodity and the same cost (or no cost) are merged, and positions
+/// whose units add up to zero are dropped. Postings are matched against the lots they reduce with
+/// [Inventory::book].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Inventory {
+    positions: Vec<Position>,
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory::default()
+    }
+
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Adds `position` to the inventory, merging it into the position with the same commodity
+    /// and cost if there is one.
+    pub fn add_position(&mut self, position: Position) {
+        match self
+            .positions
+            .iter()
+            .position(|p| p.units.currency == position.units.currency && p.cost == position.cost)
+        {
+            Some(i) => {
+                self.positions[i].units.num += position.units.num;
+                if self.positions[i].units.num.is_zero() {
+                    self.positions.remove(i);
+                }
+            }
+            None if !position.units.num.is_zero() => self.positions.push(position),
+            None => {}
+        }
+    }
+
+    /// Adds an amount held without cost.
+    pub fn add_amount(&mut self, units: Amount) {
+        self.add_position(Position { units, cost: None });
+    }
+
+    /// Books the `units` of a posting with the cost spec `cost`, dated `date`, and returns the
+    /// positions it adds to the inventory. A posting at cost against the lots held of its
+    /// commodity, e.g. `-10 IVV {}` after buying IVV, is a reduction: it takes units out of the
+    /// lots matching its spec, oldest first, at their cost. What is left, or any other posting,
+    /// adds a lot at the cost of its spec, dated `date` unless the spec has a date; a cost that
+    /// cannot be resolved is left out.
+    pub fn book(&mut self, units: Amount, cost: Option<&CostSpec>, date: &Date) -> Vec<Position> {
+        let Some(spec) = cost else {
+            self.add_amount(units.clone());
+            return vec![Position { units, cost: None }];
+        };
+        let mut lots: Vec<&Position> = self
+            .positions
+            .iter()
+            .filter(|p| {
+                p.units.currency == units.currency
+                    && p.units.num.is_sign_negative() != units.num.is_sign_negative()
+                    && p.cost.as_ref().is_some_and(|c| spec.matches(c))
+            })
+            .collect();
+        lots.sort_by(|a, b| a.cmp_by_date(b));
+
+        let mut booked = Vec::new();
+        let mut remaining = units.num;
+        for lot in lots {
+            if remaining.is_zero() {
+                break;
+            }
+            let num = if remaining.abs() < lot.units.num.abs() {
+                remaining
+            } else {
+                -lot.units.num
+            };
+            booked.push(Position {
+                units: Amount {
+                    num,
+                    currency: units.currency.clone(),
+                },
+                cost: lot.cost.clone(),
+            });
+            remaining -= num;
+        }
+        if !remaining.is_zero() {
+            let units = Amount {
+                num: remaining,
+                currency: units.currency,
+            };
+            let cost = spec.to_cost(&units, date);
+            booked.push(Position { units, cost });
+        }
+        for position in &booked {
+            self.add_position(position.clone());
+        }
+        booked
+    }
+
+    /// Returns the total number of units of `currency` held, across all lots.
+    pub fn units(&self, currency: &str) -> Decimal {
+        self.positions
+            .iter()
+            .filter(|p| p.units.currency == currency)
+            .map(|p| p.units.num)
+            .sum()
+    }
+
+    /// Reduces the lot of `units.currency` whose cost has the given label, as a posting like
+    /// `-20 IVV {"ref-001"}` does. `units` is signed like the posting, so it must have the
+    /// opposite sign of the lot. Returns the positions taken out of the inventory.
+    pub fn reduce_by_label(
+        &mut self,
+        units: Amount,
+        label: &str,
+    ) -> Result<Vec<Position>, BookingError> {
+        let matches: Vec<usize> = self
+            .positions
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                p.units.currency == units.currency
+                    && p.cost.as_ref().and_then(|c| c.label.as_deref()) == Some(label)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let i = match matches[..] {
+            [i] => i,
+            [] => return Err(BookingError::NoMatch(label.to_string())),
+            _ => return Err(BookingError::Ambiguous(label.to_string())),
+        };
+
+        let lot = &self.positions[i];
+        if lot.units.num.is_sign_negative() == units.num.is_sign_negative()
+            || units.num.abs() > lot.units.num.abs()
+        {
+            return Err(BookingError::InsufficientUnits(label.to_string()));
+        }
+        let reduced = Position {
+            units: units.clone(),
+            cost: lot.cost.clone(),
+        };
+        self.add_position(reduced.clone());
+        Ok(vec![reduced])
+    }
+}
+
+/// Renders the positions separated by commas, sorted by currency, then with the positions held
+/// without cost first and the lots by date, cost and label, so the output does not depend on
+/// the order the positions were added in.
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut positions: Vec<&Position> = self.positions.iter().collect();
+        positions.sort_by_key(|p| {
+            let cost = p
+                .cost
+                .as_ref()
+                .map(|c| (&c.date, c.number, &c.currency, &c.label));
+            (&p.units.currency, cost)
+        });
+        for (i, position) in positions.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", position)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors raised when a reduction cannot be matched against the lots of an inventory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BookingError {
+    /// No lot matches the reduction.
+    NoMatch(String),
+    /// Several lots match the reduction and none can be chosen.
+    Ambiguous(String),
+    /// The matching lot holds fewer units than the reduction takes out.
+    InsufficientUnits(String),
+}
+
+impl fmt::Display for BookingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookingError::NoMatch(s) => write!(f, "no lot matches {}", s),
+            BookingError::Ambiguous(s) => write!(f, "several lots match {}", s),
+            BookingError::InsufficientUnits(s) => write!(f, "not enough units in lot {}", s),
+        }
+    }
+}
+
+impl error::Error for BookingError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{date::Date, directives::position::Cost};
+
+    use super::*;
+
+    #[test]
+    fn test_add_position() {
+        let lot = Cost::builder()
+            .number(Decimal::new(18307, 2))
+            .currency("USD".to_string())
+            .date(Date::from_str_unchecked("2014-02-11"))
+            .label(None)
+            .build();
+        let mut inventory = Inventory::new();
+        inventory.add_amount(Amount::from((Decimal::from(3200), "USD")));
+        inventory.add_amount(Amount::from((Decimal::from(-1200), "USD")));
+        inventory.add_position(Position {
+            units: Amount::from((Decimal::from(20), "IVV")),
+            cost: Some(lot.clone()),
+        });
+        inventory.add_amount(Amount::from((Decimal::from(5), "IVV")));
+
+        assert_eq!(inventory.positions().len(), 3);
+        assert_eq!(inventory.units("USD"), Decimal::from(2000));
+        assert_eq!(inventory.units("IVV"), Decimal::from(25));
+
+        inventory.add_position(Position {
+            units: Amount::from((Decimal::from(-20), "IVV")),
+            cost: Some(lot),
+        });
+        inventory.add_amount(Amount::from((Decimal::from(-2000), "USD")));
+        assert_eq!(
+            inventory.positions(),
+            &[Position {
+                units: Amount::from((Decimal::from(5), "IVV")),
+                cost: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_book() {
+        let date = Date::from_str_unchecked("2014-07-11");
+        let units = |num: i64| Amount::from((Decimal::from(num), "IVV"));
+        let spec = |s: &str| s.parse::<CostSpec>().unwrap();
+        let mut inventory = Inventory::new();
+        inventory.book(
+            units(10),
+            Some(&spec("{183.07 USD}")),
+            &Date::from_str_unchecked("2014-02-11"),
+        );
+        inventory.book(
+            units(15),
+            Some(&spec("{187.12 USD}")),
+            &Date::from_str_unchecked("2014-03-22"),
+        );
+
+        let sold = inventory.book(units(-10), Some(&spec("{183.07 USD}")), &date);
+        assert_eq!(sold.len(), 1);
+        assert_eq!(sold[0].to_string(), "-10 IVV {183.07 USD, 2014-02-11}");
+        assert_eq!(inventory.to_string(), "15 IVV {187.12 USD, 2014-03-22}");
+
+        let sold = inventory.book(units(-5), Some(&spec("{}")), &date);
+        assert_eq!(sold[0].to_string(), "-5 IVV {187.12 USD, 2014-03-22}");
+        assert_eq!(inventory.to_string(), "10 IVV {187.12 USD, 2014-03-22}");
+
+        inventory.book(units(5), Some(&spec("{190.00 USD}")), &date);
+        let sold = inventory.book(units(-12), Some(&spec("{}")), &date);
+        let sold: Vec<String> = sold.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            sold,
+            vec![
+                "-10 IVV {187.12 USD, 2014-03-22}",
+                "-2 IVV {190.00 USD, 2014-07-11}",
+            ]
+        );
+        assert_eq!(inventory.to_string(), "3 IVV {190.00 USD, 2014-07-11}");
+
+        inventory.book(Amount::from((Decimal::from(-100), "USD")), None, &date);
+        assert_eq!(inventory.units("USD"), Decimal::from(-100));
+    }
+
+    #[test]
+    fn test_reduce_by_label() {
+        let lot = |units: i64, number: i64, date: &str, label: Option<&str>| Position {
+            units: Amount::from((Decimal::from(units), "IVV")),
+            cost: Some(
+                Cost::builder()
+                    .number(Decimal::new(number, 2))
+                    .currency("USD".to_string())
+                    .date(Date::from_str_unchecked(date))
+                    .label(label.map(|l| l.to_string()))
+                    .build(),
+            ),
+        };
+        let mut inventory = Inventory::new();
+        inventory.add_position(lot(20, 18307, "2014-02-11", Some("ref-001")));
+        inventory.add_position(lot(15, 18712, "2014-03-22", None));
+
+        let reduced = inventory
+            .reduce_by_label(Amount::from((Decimal::from(-20), "IVV")), "ref-001")
+            .unwrap();
+        assert_eq!(
+            reduced,
+            vec![lot(-20, 18307, "2014-02-11", Some("ref-001"))]
+        );
+        assert_eq!(inventory.positions(), &[lot(15, 18712, "2014-03-22", None)]);
+
+        assert_eq!(
+            inventory.reduce_by_label(Amount::from((Decimal::from(-20), "IVV")), "ref-001"),
+            Err(BookingError::NoMatch("ref-001".to_string()))
+        );
+
+        inventory.add_position(lot(5, 18000, "2014-04-01", Some("ref-002")));
+        inventory.add_position(lot(5, 18100, "2014-04-02", Some("ref-002")));
+        assert_eq!(
+            inventory.reduce_by_label(Amount::from((Decimal::from(-5), "IVV")), "ref-002"),
+            Err(BookingError::Ambiguous("ref-002".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_display_sorted() {
+        let lot = Position {
+            units: Amount::from((Decimal::from(20), "IVV")),
+            cost: Some(
+                Cost::builder()
+                    .number(Decimal::new(18307, 2))
+                    .currency("USD".to_string())
+                    .date(Date::from_str_unchecked("2014-02-11"))
+                    .label(Some("ref-001".to_string()))
+                    .build(),
+            ),
+        };
+        let mut inventory = Inventory::new();
+        inventory.add_amount(Amount::from((Decimal::new(10050, 2), "USD")));
+        inventory.add_position(lot.clone());
+        inventory.add_amount(Amount::from((Decimal::from(30), "EUR")));
+        inventory.add_amount(Amount::from((Decimal::from(5), "IVV")));
+
+        let mut reordered = Inventory::new();
+        reordered.add_amount(Amount::from((Decimal::from(5), "IVV")));
+        reordered.add_amount(Amount::from((Decimal::from(30), "EUR")));
+        reordered.add_position(lot);
+        reordered.add_amount(Amount::from((Decimal::new(10050, 2), "USD")));
+
+        let expected = r#"30 EUR, 5 IVV, 20 IVV {183.07 USD, 2014-02-11, "ref-001"}, 100.50 USD"#;
+        assert_eq!(inventory.to_string(), expected);
+        assert_eq!(reordered.to_string(), expected);
+    }
+}
@@ -1,13 +1,31 @@
-use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
 
 use rust_decimal::Decimal;
 
-/// Metadata that can be attached to other Beancount information.
+use crate::util::escape_string;
+
+/// Metadata that can be attached to other Beancount information. This is a `HashMap`, or a
+/// `BTreeMap` when the `std` feature is disabled.
+#[cfg(feature = "std")]
 pub type Meta = HashMap<String, MetaValue>;
+/// Metadata that can be attached to other Beancount information. This is a `HashMap`, or a
+/// `BTreeMap` when the `std` feature is disabled.
+#[cfg(not(feature = "std"))]
+pub type Meta = BTreeMap<String, MetaValue>;
 
 /// An enum of the valid values in a metadata map.
-// TODO: Implement Display
+///
+/// With the `serde` feature, values serialize tagged with their variant, e.g.
+/// `{"type": "Number", "value": "42"}`, so that numbers, amounts and currencies stay distinct.
 #[derive(Eq, PartialEq, Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
 pub enum MetaValue {
     Text(String),
     Account(super::account::Account),
@@ -19,6 +37,36 @@ pub enum MetaValue {
     Number(Decimal),
 }
 
+/// Renders the value as written after a metadata key: text is quoted, tags keep their `#` and
+/// booleans are `TRUE` or `FALSE`.
+impl fmt::Display for MetaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetaValue::Text(s) => write!(f, "\"{}\"", escape_string(s)),
+            MetaValue::Account(a) => write!(f, "{}", a),
+            MetaValue::Date(d) => write!(f, "{}", d),
+            MetaValue::Currency(c) => write!(f, "{}", c),
+            MetaValue::Tag(t) => write!(f, "#{}", t),
+            MetaValue::Bool(true) => write!(f, "TRUE"),
+            MetaValue::Bool(false) => write!(f, "FALSE"),
+            MetaValue::Amount(a) => write!(f, "{}", a),
+            MetaValue::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// Writes `meta` as beancount metadata lines, sorted by key so the output is stable. Each line
+/// starts with a newline followed by `indent`, so the caller writes the line the metadata belongs
+/// to without a trailing newline.
+pub fn write_meta(f: &mut impl fmt::Write, meta: &Meta, indent: &str) -> fmt::Result {
+    let mut entries: Vec<(&String, &MetaValue)> = meta.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    for (key, value) in entries {
+        write!(f, "\n{}{}: {}", indent, key, value)?;
+    }
+    Ok(())
+}
+
 /// Tag associated with a transaction directive.  Tags allow you to mark a subset of transactions,
 /// enabling filtering on a tag(s) when generating a report.
 ///
@@ -34,6 +82,15 @@ pub enum MetaValue {
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.oivvp5olom2v>
 pub type Tag = String;
 
+/// The tags of a transaction. This is a `HashSet`, or a `BTreeSet` when the `std` feature is
+/// disabled.
+#[cfg(feature = "std")]
+pub type Tags = HashSet<Tag>;
+/// The tags of a transaction. This is a `HashSet`, or a `BTreeSet` when the `std` feature is
+/// disabled.
+#[cfg(not(feature = "std"))]
+pub type Tags = BTreeSet<Tag>;
+
 /// Links provide a way to link transactions together.  You may think of the link as a special kind
 /// of tag that can be used to group together a set of financially related transactions over time.
 ///
@@ -54,3 +111,59 @@ pub type Tag = String;
 ///
 /// <https://docs.google.com/document/d/1wAMVrKIA2qtRGmoVDSUBJGmYZSygUaR0uOMW1GV3YE0/edit#heading=h.k4v5vkjukel7>
 pub type Link = String;
+
+/// The links of a transaction. This is a `HashSet`, or a `BTreeSet` when the `std` feature is
+/// disabled.
+#[cfg(feature = "std")]
+pub type Links = HashSet<Link>;
+/// The links of a transaction. This is a `HashSet`, or a `BTreeSet` when the `std` feature is
+/// disabled.
+#[cfg(not(feature = "std"))]
+pub type Links = BTreeSet<Link>;
+
+#[cfg(test)]
+mod tests {
+    use crate::amount::Amount;
+
+    use super::*;
+
+    #[test]
+    fn test_write_meta() {
+        let mut meta = Meta::new();
+        meta.insert(
+            "name".to_string(),
+            MetaValue::Text("Hooli \"Class C\"".to_string()),
+        );
+        meta.insert("precision".to_string(), MetaValue::Number(Decimal::from(2)));
+        meta.insert("active".to_string(), MetaValue::Bool(true));
+        meta.insert(
+            "limit".to_string(),
+            MetaValue::Amount(Amount::from((Decimal::new(15420, 2), "USD"))),
+        );
+
+        let mut res = String::from("2012-01-01 commodity HOOL");
+        write_meta(&mut res, &meta, "  ").unwrap();
+        assert_eq!(
+            res,
+            "2012-01-01 commodity HOOL\n  active: TRUE\n  limit: 154.20 USD\n  name: \"Hooli \\\"Class C\\\"\"\n  precision: 2"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_meta_value_serde_round_trip() {
+        let values = vec![
+            MetaValue::Amount(Amount::from((Decimal::new(15420, 2), "USD"))),
+            MetaValue::Number(Decimal::new(15420, 2)),
+            MetaValue::Currency("USD".to_string()),
+            MetaValue::Text("USD".to_string()),
+        ];
+        for value in values {
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<MetaValue>(&json).unwrap(), value);
+        }
+
+        let json = serde_json::to_value(MetaValue::Number(Decimal::from(42))).unwrap();
+        assert_eq!(json["type"], "Number");
+    }
+}
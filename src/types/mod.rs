@@ -3,5 +3,9 @@ pub mod amount;
 pub mod currency;
 pub mod date;
 pub mod directives;
+pub mod error;
 pub mod flags;
+pub mod inventory;
 pub mod metadata;
+pub mod price_db;
+pub mod util;
@@ -0,0 +1,204 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use rust_decimal::Decimal;
+
+use crate::amount::Amount;
+use crate::currency::{Currency, CurrencyPair};
+use crate::date::Date;
+use crate::directives::prices::Price;
+
+/// An in-memory database of prices, built from `price` directives.
+///
+/// Prices are stored per day and per currency pair. When several prices are declared for the
+/// same pair on the same day, the last one wins, as in beancount.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PriceDB {
+    prices: BTreeMap<CurrencyPair, BTreeMap<Date, Decimal>>,
+}
+
+impl PriceDB {
+    pub fn new() -> PriceDB {
+        PriceDB::default()
+    }
+
+    /// Builds the database from `prices`, also recording the reciprocal of every rate for the
+    /// inverse pair, e.g. `CAD/USD` at `1/1.08` from `USD 1.08 CAD`. Pairs for which a price is
+    /// declared in either direction keep the declared prices only.
+    pub fn with_inverses<'a, 'b: 'a>(prices: impl IntoIterator<Item = &'a Price<'b>>) -> PriceDB {
+        let mut db: PriceDB = prices.into_iter().collect();
+        let mut inverses = Vec::new();
+        for (pair, rates) in &db.prices {
+            let inverse = CurrencyPair::new(pair.quote.clone(), pair.base.clone());
+            if db.prices.contains_key(&inverse) {
+                continue;
+            }
+            for (date, rate) in rates {
+                if let Some(reciprocal) = Decimal::ONE.checked_div(*rate) {
+                    inverses.push((inverse.clone(), date.clone(), reciprocal));
+                }
+            }
+        }
+        for (pair, date, rate) in inverses {
+            db.insert(pair, date, rate);
+        }
+        db
+    }
+
+    /// Records that on `date`, one unit of `pair.base` is worth `rate` units of `pair.quote`.
+    pub fn insert(&mut self, pair: CurrencyPair, date: Date, rate: Decimal) {
+        self.prices.entry(pair).or_default().insert(date, rate);
+    }
+
+    /// Returns the most recent rate for `pair` declared on or before `date`.
+    pub fn get_price(&self, pair: &CurrencyPair, date: &Date) -> Option<Decimal> {
+        self.prices
+            .get(pair)
+            .and_then(|rates| rates.range(..=date.clone()).next_back())
+            .map(|(_, rate)| *rate)
+    }
+
+    /// Converts `amount` into `target` at the rate of `date`, using the price of the pair in
+    /// either direction. Returns `None` if no price is known for the pair.
+    pub fn convert(&self, amount: &Amount, target: &Currency, date: &Date) -> Option<Amount> {
+        if &amount.currency == target {
+            return Some(amount.clone());
+        }
+        let direct = CurrencyPair::new(amount.currency.clone(), target.clone());
+        let num = match self.get_price(&direct, date) {
+            Some(rate) => amount.num * rate,
+            None => {
+                let inverse = CurrencyPair::new(target.clone(), amount.currency.clone());
+                amount.num.checked_div(self.get_price(&inverse, date)?)?
+            }
+        };
+        Some(Amount {
+            num,
+            currency: target.clone(),
+        })
+    }
+
+    /// Compares two amounts, possibly in different currencies, by their value in `target` at
+    /// the rates of `date`. Returns `None` if either cannot be converted.
+    pub fn cmp_amounts(
+        &self,
+        a: &Amount,
+        b: &Amount,
+        target: &Currency,
+        date: &Date,
+    ) -> Option<Ordering> {
+        let a = self.convert(a, target, date)?;
+        let b = self.convert(b, target, date)?;
+        Some(a.num.cmp(&b.num))
+    }
+
+    /// Returns the currency pairs for which at least one price is known.
+    pub fn pairs(&self) -> impl Iterator<Item = &CurrencyPair> {
+        self.prices.keys()
+    }
+}
+
+impl<'a, 'b> FromIterator<&'a Price<'b>> for PriceDB {
+    fn from_iter<I: IntoIterator<Item = &'a Price<'b>>>(prices: I) -> Self {
+        let mut db = PriceDB::new();
+        for price in prices {
+            db.insert(
+                CurrencyPair::new(price.currency.clone(), price.amount.currency.clone()),
+                price.date.clone(),
+                price.amount.num,
+            );
+        }
+        db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use super::*;
+
+    fn price(date: &str, currency: &str, num: &str, quote: &str) -> Price<'static> {
+        Price::builder()
+            .date(Date::from_str_unchecked(date))
+            .currency(Currency::from(currency))
+            .amount(
+                Amount::builder()
+                    .num(Decimal::from_str(num).unwrap())
+                    .currency(Currency::from(quote))
+                    .build(),
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_get_price() {
+        let prices = [
+            price("2014-07-09", "USD", "1.08", "CAD"),
+            price("2014-07-11", "USD", "1.09", "CAD"),
+            price("2014-07-11", "USD", "1.10", "CAD"),
+        ];
+        let db: PriceDB = prices.iter().collect();
+        let pair: CurrencyPair = "USD/CAD".parse().unwrap();
+
+        let on = |date: &str| db.get_price(&pair, &Date::from_str_unchecked(date));
+        assert_eq!(on("2014-07-08"), None);
+        assert_eq!(on("2014-07-10"), Some(Decimal::from_str("1.08").unwrap()));
+        assert_eq!(on("2014-08-01"), Some(Decimal::from_str("1.10").unwrap()));
+        assert_eq!(
+            db.get_price(
+                &"CAD/USD".parse().unwrap(),
+                &Date::from_str_unchecked("2014-08-01")
+            ),
+            None
+        );
+        assert_eq!(db.pairs().collect::<Vec<_>>(), vec![&pair]);
+    }
+
+    #[test]
+    fn test_cmp_amounts() {
+        let db: PriceDB = [
+            price("2014-07-09", "HOOL", "520.00", "USD"),
+            price("2014-07-09", "USD", "1.08", "CAD"),
+        ]
+        .iter()
+        .collect();
+        let date = Date::from_str_unchecked("2014-07-10");
+        let usd = Currency::from("USD");
+        let amount = |s: &str| s.parse::<Amount>().unwrap();
+
+        assert_eq!(
+            db.cmp_amounts(&amount("10 HOOL"), &amount("100 USD"), &usd, &date),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            db.cmp_amounts(&amount("108 CAD"), &amount("100.00 USD"), &usd, &date),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            db.cmp_amounts(&amount("10 HOOL"), &amount("100 EUR"), &usd, &date),
+            None
+        );
+    }
+
+    #[test]
+    fn test_with_inverses() {
+        let prices = [
+            price("2014-07-09", "USD", "1.08", "CAD"),
+            price("2014-07-09", "EUR", "1.35", "USD"),
+            price("2014-07-09", "USD", "0.75", "EUR"),
+        ];
+        let db = PriceDB::with_inverses(&prices);
+        let date = Date::from_str_unchecked("2014-07-10");
+        let get = |pair: &str| db.get_price(&pair.parse().unwrap(), &date);
+
+        assert_eq!(get("USD/CAD"), Some(Decimal::from_str("1.08").unwrap()));
+        assert_eq!(
+            get("CAD/USD"),
+            Some(Decimal::ONE / Decimal::from_str("1.08").unwrap())
+        );
+        assert_eq!(get("USD/EUR"), Some(Decimal::from_str("0.75").unwrap()));
+        assert_eq!(db.pairs().count(), 4);
+    }
+}
@@ -0,0 +1,60 @@
+use alloc::string::String;
+
+/// Escapes a string so it can be written between double quotes in a beancount file. Backslashes
+/// and double quotes are prefixed with a backslash; everything else, including newlines, is kept
+/// as is.
+pub fn escape_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Reads a string as written in a beancount file: surrounding whitespace is trimmed, surrounding
+/// double quotes are stripped, and escaped characters are unescaped. This is the inverse of
+/// `escape_string`.
+pub fn unquote_string(raw: &str) -> String {
+    let trimmed = raw.trim();
+    let inner = trimmed
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(trimmed);
+    let mut res = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                res.push(escaped);
+                continue;
+            }
+        }
+        res.push(c);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_string() {
+        assert_eq!(escape_string("Lamb tagine"), "Lamb tagine");
+        assert_eq!(escape_string(r#"The "Ritz""#), r#"The \"Ritz\""#);
+        assert_eq!(escape_string(r"C:\stmts"), r"C:\\stmts");
+        assert_eq!(escape_string("two\nlines"), "two\nlines");
+    }
+
+    #[test]
+    fn test_unquote_string() {
+        assert_eq!(unquote_string("  \"Lamb tagine\" "), "Lamb tagine");
+        assert_eq!(unquote_string(r#""The \"Ritz\"""#), r#"The "Ritz""#);
+        assert_eq!(unquote_string(r#""C:\\stmts""#), r"C:\stmts");
+        assert_eq!(unquote_string("Lamb tagine"), "Lamb tagine");
+        assert_eq!(unquote_string(&escape_string(r#"a "b" \c"#)), r#"a "b" \c"#);
+    }
+}